@@ -0,0 +1,1365 @@
+use anyhow::{anyhow, bail, Context, Result};
+use error_code::ErrorCode;
+use message::tr;
+use parser::expression::{self, Direction, Expression};
+use parser::{is_symbol_name, Directive, InstructionType, Location, Parser, SourceLine};
+use ram::{RamAllocator, VARIABLE_RAM_START};
+use rules::Rule;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use symbol_table::{SymbolKind, SymbolTable};
+use usage::Usage;
+
+pub mod lint;
+mod prologue;
+mod ram;
+pub mod rules;
+mod usage;
+
+// A命令で扱える値の最大値(15ビット)
+const MAX_A_VALUE: u16 = 0x7FFF;
+// ROMのワード数
+const ROM_SIZE: usize = 0x8000;
+// `.record`で定義した構造体のフィールドの名前の区切り
+const RECORD_FIELD_SEPARATOR: char = '.';
+// `(.loop)`のように始まるラベルは直前のグローバルラベルをスコープとするローカルラベル
+const SCOPED_LABEL_PREFIX: char = '.';
+
+/// アセンブルの設定
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// `.var`で宣言していないシンボルを変数として使うとエラーにする
+    pub strict: bool,
+    /// `.data`/`.string`の初期値をRAMに書き込むコードを0番地に置く
+    pub init_prologue: bool,
+    /// 変数を割り当て始めるRAMアドレス
+    pub variable_base: u16,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            init_prologue: false,
+            variable_base: VARIABLE_RAM_START,
+        }
+    }
+}
+
+/// アセンブルは続けられるが利用者に知らせる問題
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub location: Location,
+    pub message: String,
+    /// 水準を設定するための規則
+    pub rule: Rule,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.location, tr!(warning), self.message)
+    }
+}
+
+// ソース文字列をアセンブルして機械語を返す
+pub fn assemble_source(source: &str, symbol_table: &mut SymbolTable) -> Result<Vec<u16>> {
+    let lines = preprocess(parser::include::expand(parser::source_lines(source), &[])?)?;
+    let options = Options::default();
+    let definitions = first_pass(
+        &mut Parser::from_lines(lines.clone()),
+        symbol_table,
+        &options,
+    )?;
+    second_pass(
+        &mut Parser::from_lines(lines),
+        symbol_table,
+        &definitions,
+        &options,
+        &mut Vec::new(),
+    )
+}
+
+// first_passの前にマクロを展開する
+pub fn preprocess(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>> {
+    parser::macros::expand(lines)
+}
+
+/// `1:`のような数値ローカルラベルの定義位置
+///
+/// 同じ番号を何度でも定義できる一時的なラベルなので、SymbolTableには登録しない。
+#[derive(Debug, Default)]
+pub struct LocalLabels {
+    addresses: HashMap<u32, Vec<u16>>,
+}
+
+impl LocalLabels {
+    fn define(&mut self, label: u32, address: u16) {
+        self.addresses.entry(label).or_default().push(address);
+    }
+
+    // addressの命令から見て前方/後方で最も近い定義のアドレスを返す
+    fn resolve(&self, label: u32, direction: Direction, address: u16) -> Option<u16> {
+        let addresses = self.addresses.get(&label)?;
+        match direction {
+            //命令の直後のラベルはaddress+1、直前のラベルはaddressを指す
+            Direction::Forward => addresses.iter().find(|a| **a > address).copied(),
+            Direction::Backward => addresses.iter().rev().find(|a| **a <= address).copied(),
+        }
+    }
+}
+
+/// first_passで集めた、second_passで使う情報
+#[derive(Debug)]
+pub struct Definitions {
+    local_labels: LocalLabels,
+    // `.var`で宣言した変数に割り当てたRAM
+    ram: RamAllocator,
+    // `.data`/`.string`で宣言したRAMの(アドレス, 初期値)
+    ram_data: Vec<(u16, u16)>,
+    // 利用者のコードを置き始めるROMアドレス(初期化コードの直後)
+    code_start: u16,
+}
+
+impl Definitions {
+    /// `.data`/`.string`で宣言したRAMの(アドレス, 初期値)をアドレス順に返す
+    pub fn ram_data(&self) -> Vec<(u16, u16)> {
+        let mut ram_data = self.ram_data.clone();
+        ram_data.sort();
+        ram_data
+    }
+}
+
+pub fn first_pass(
+    asm_parser: &mut Parser,
+    symbol_table: &mut SymbolTable,
+    options: &Options,
+) -> Result<Definitions> {
+    //初期化コードを置く場合は、その後ろから利用者のコードを配置する
+    let mut row_number: u16 = if options.init_prologue {
+        let size = prologue::size(data_words(&mut asm_parser.clone())?);
+        if size > ROM_SIZE {
+            bail!(ErrorCode::RomOverflow.error(tr!(rom_overflow, size, ROM_SIZE)));
+        }
+        size as u16
+    } else {
+        0
+    };
+    //R0〜R15には変数を置かない。ただし設定でそれより前から割り当てる場合はその番地から
+    let mut ram = RamAllocator::new(VARIABLE_RAM_START.min(options.variable_base));
+    ram.set_origin(options.variable_base)
+        .with_context(|| tr!(invalid_variable_base))?;
+    let mut definitions = Definitions {
+        local_labels: LocalLabels::default(),
+        ram,
+        ram_data: Vec::new(),
+        code_start: row_number,
+    };
+    let mut scope: Option<String> = None;
+
+    while asm_parser.has_more_lines()? {
+        asm_parser.advance()?;
+        match asm_parser
+            .instruction_type()
+            .with_context(|| asm_parser.location())?
+        {
+            Some(InstructionType::L) => {
+                let symbol = asm_parser
+                    .symbol()
+                    .with_context(|| asm_parser.location())?
+                    .unwrap();
+                match symbol.parse::<u32>() {
+                    Ok(label) => definitions.local_labels.define(label, row_number),
+                    Err(_) => {
+                        let name = qualify(&symbol, scope.as_deref())
+                            .with_context(|| asm_parser.location())?;
+                        symbol_table
+                            .add_symbol(&name, row_number, SymbolKind::Label)
+                            .with_context(|| asm_parser.location())?;
+                        symbol_table.set_definition(&name, asm_parser.symbol_location(&name));
+                        if opens_scope(&symbol) {
+                            scope = Some(symbol);
+                        }
+                    }
+                }
+            }
+            Some(InstructionType::A) | Some(InstructionType::C) => {
+                if usize::from(row_number) >= ROM_SIZE {
+                    let size = usize::from(row_number) + 1;
+                    return Err(anyhow!(ErrorCode::RomOverflow.error(tr!(
+                        rom_overflow,
+                        size,
+                        ROM_SIZE
+                    ))))
+                    .with_context(|| asm_parser.location());
+                }
+                row_number += 1;
+            }
+            Some(InstructionType::Directive) => {
+                define(
+                    asm_parser,
+                    symbol_table,
+                    &mut definitions,
+                    row_number,
+                    scope.as_deref(),
+                )
+                .with_context(|| asm_parser.location())?;
+            }
+            None => (),
+        }
+
+        if !(asm_parser.has_more_lines()?) {
+            break;
+        }
+    }
+
+    Ok(definitions)
+}
+
+// `.data`/`.string`で初期値を与えるワード数
+fn data_words(asm_parser: &mut Parser) -> Result<usize> {
+    let mut count = 0;
+    while asm_parser.has_more_lines()? {
+        asm_parser.advance()?;
+        match asm_parser
+            .directive()
+            .with_context(|| asm_parser.location())?
+        {
+            Some(Directive::Data { values, .. }) => count += values.len(),
+            Some(Directive::String { text, .. }) => count += text.len() + 1,
+            _ => (),
+        }
+    }
+
+    Ok(count)
+}
+
+// ディレクティブによるシンボルの定義
+fn define(
+    asm_parser: &Parser,
+    symbol_table: &mut SymbolTable,
+    definitions: &mut Definitions,
+    address: u16,
+    scope: Option<&str>,
+) -> Result<()> {
+    let evaluate_text = |text: &str, symbol_table: &mut SymbolTable, definitions: &Definitions| {
+        let expression = parse_argument(text, symbol_table, scope, asm_parser)?;
+        evaluate(
+            &expression,
+            symbol_table,
+            &definitions.local_labels,
+            address,
+        )
+    };
+    match asm_parser.directive()?.unwrap() {
+        Directive::Equ { name, value } => {
+            if !is_symbol_name(&name) {
+                bail!(ErrorCode::InvalidSymbolName.error(tr!(invalid_constant_name, name)));
+            }
+            // 値の式にはそれまでに定義されたシンボルだけを使える
+            let value = evaluate_text(&value, symbol_table, definitions)?;
+            symbol_table.add_symbol(&name, value, SymbolKind::Constant)?;
+            symbol_table.set_definition(&name, asm_parser.symbol_location(&name));
+            Ok(())
+        }
+        Directive::Var { name, size } => {
+            if !is_symbol_name(&name) || is_scoped_label(&name) {
+                bail!(ErrorCode::InvalidSymbolName.error(tr!(invalid_variable_name, name)));
+            }
+            if let Some(kind) = symbol_table.kind(&name) {
+                bail!(ErrorCode::DuplicateSymbol.error(tr!(
+                    already_defined,
+                    name,
+                    kind.description()
+                )));
+            }
+            let size = match size {
+                Some(size) => evaluate_text(&size, symbol_table, definitions)?,
+                None => 1,
+            };
+            let ram_address = definitions.ram.allocate(&name, size)?;
+            symbol_table.add_variable(&name, ram_address, size)?;
+            symbol_table.set_definition(&name, asm_parser.symbol_location(&name));
+            Ok(())
+        }
+        Directive::Org { address: origin } => {
+            let origin = evaluate_text(&origin, symbol_table, definitions)?;
+            definitions.ram.set_origin(origin)
+        }
+        Directive::Record { name, fields } => {
+            if !is_symbol_name(&name) || is_scoped_label(&name) {
+                bail!(ErrorCode::InvalidSymbolName.error(tr!(invalid_record_name, name)));
+            }
+            //各フィールドのオフセットを`名前.フィールド`、全体のワード数を`名前`の定数にする
+            let mut offset: u16 = 0;
+            for (field, size) in fields {
+                if !is_symbol_name(&field) {
+                    bail!(ErrorCode::InvalidSymbolName.error(tr!(invalid_field_name, field)));
+                }
+                let size = match size {
+                    Some(size) => evaluate_text(&size, symbol_table, definitions)?,
+                    None => 1,
+                };
+                if size == 0 {
+                    bail!(ErrorCode::InvalidSize.error(tr!(field_size, field)));
+                }
+                let field_name = format!("{}{}{}", name, RECORD_FIELD_SEPARATOR, field);
+                symbol_table.add_symbol(&field_name, offset, SymbolKind::Constant)?;
+                symbol_table.set_definition(&field_name, asm_parser.symbol_location(&field));
+                offset = match offset.checked_add(size) {
+                    Some(offset) if offset <= MAX_A_VALUE => offset,
+                    _ => bail!(ErrorCode::InvalidSize.error(tr!(record_too_large, name))),
+                };
+            }
+            symbol_table.add_symbol(&name, offset, SymbolKind::Constant)?;
+            symbol_table.set_definition(&name, asm_parser.symbol_location(&name));
+            Ok(())
+        }
+        Directive::Data { name, values } => {
+            let values = values
+                .iter()
+                .map(|value| {
+                    let expression = parse_argument(value, symbol_table, scope, asm_parser)?;
+                    let value = evaluate_value(
+                        &expression,
+                        symbol_table,
+                        &definitions.local_labels,
+                        address,
+                    )?;
+                    // 負の値は2の補数で格納する
+                    match value {
+                        -0x8000..=0xFFFF => Ok(value as u16),
+                        _ => bail!(ErrorCode::ValueOutOfRange.error(tr!(data_out_of_range, value))),
+                    }
+                })
+                .collect::<Result<Vec<u16>>>()?;
+            let location = asm_parser.symbol_location(&name);
+            define_data(symbol_table, definitions, &name, values, location)
+        }
+        Directive::String { name, text } => {
+            //文字列の終わりを示す0を付ける
+            let values = text.bytes().map(u16::from).chain([0]).collect();
+            let location = asm_parser.symbol_location(&name);
+            define_data(symbol_table, definitions, &name, values, location)
+        }
+    }
+}
+
+// 初期値を持つ変数を宣言する
+fn define_data(
+    symbol_table: &mut SymbolTable,
+    definitions: &mut Definitions,
+    name: &str,
+    values: Vec<u16>,
+    location: Location,
+) -> Result<()> {
+    if !is_symbol_name(name) || is_scoped_label(name) {
+        bail!(ErrorCode::InvalidSymbolName.error(tr!(invalid_variable_name, name)));
+    }
+    if let Some(kind) = symbol_table.kind(name) {
+        bail!(ErrorCode::DuplicateSymbol.error(tr!(already_defined, name, kind.description())));
+    }
+    let ram_address = definitions.ram.allocate(name, values.len() as u16)?;
+    symbol_table.add_variable(name, ram_address, values.len() as u16)?;
+    symbol_table.set_definition(name, location);
+    definitions.ram_data.extend((ram_address..).zip(values));
+    Ok(())
+}
+
+// ディレクティブの引数の式を解析し、参照したシンボルの位置を記録する
+fn parse_argument(
+    text: &str,
+    symbol_table: &mut SymbolTable,
+    scope: Option<&str>,
+    asm_parser: &Parser,
+) -> Result<Expression> {
+    let expression = resolve_scoped_labels(expression::parse(text)?, scope, symbol_table)?;
+    add_references(&expression, symbol_table, asm_parser);
+    Ok(expression)
+}
+
+// 式の中のシンボルを参照した位置を記録する
+fn add_references(expression: &Expression, symbol_table: &mut SymbolTable, asm_parser: &Parser) {
+    match expression {
+        Expression::Symbol(symbol) => {
+            symbol_table.add_reference(symbol, asm_parser.symbol_location(symbol))
+        }
+        Expression::Negate(operand) => add_references(operand, symbol_table, asm_parser),
+        Expression::Binary(_, left, right) => {
+            add_references(left, symbol_table, asm_parser);
+            add_references(right, symbol_table, asm_parser);
+        }
+        _ => (),
+    }
+}
+
+fn is_scoped_label(symbol: &str) -> bool {
+    symbol.starts_with(SCOPED_LABEL_PREFIX)
+}
+
+// ラベルが新しいスコープを開くか。マクロ展開で付けられたラベルはスコープを変えない
+fn opens_scope(label: &str) -> bool {
+    !is_scoped_label(label) && !label.contains(parser::macros::LOCAL_LABEL_SEPARATOR)
+}
+
+// `.loop`のようなローカルラベルを、スコープとなるラベルを付けた`PARENT.loop`に変換する
+fn qualify(symbol: &str, scope: Option<&str>) -> Result<String> {
+    match scope {
+        _ if !is_scoped_label(symbol) => Ok(symbol.to_string()),
+        Some(scope) => Ok(format!("{}{}", scope, symbol)),
+        None => bail!(ErrorCode::LabelOutOfScope.error(tr!(no_enclosing_label, symbol))),
+    }
+}
+
+// 式の中のローカルラベルの参照を、現在のスコープで定義されたラベルの名前に置き換える
+fn resolve_scoped_labels(
+    expression: Expression,
+    scope: Option<&str>,
+    symbol_table: &SymbolTable,
+) -> Result<Expression> {
+    match expression {
+        Expression::Symbol(symbol) if is_scoped_label(&symbol) => {
+            let name = qualify(&symbol, scope)?;
+            if symbol_table.contains(&name)? {
+                return Ok(Expression::Symbol(name));
+            }
+            //他のスコープにある同じ名前のラベルは完全な名前でしか参照できない
+            let candidates: Vec<String> = symbol_table
+                .symbols(SymbolKind::Label)
+                .into_iter()
+                .map(|(label, _)| label)
+                .filter(|label| label.len() > symbol.len() && label.ends_with(&symbol))
+                .collect();
+            if candidates.is_empty() {
+                bail!(ErrorCode::LabelOutOfScope.error(tr!(not_in_scope, symbol, scope.unwrap())));
+            }
+            bail!(ErrorCode::LabelOutOfScope.error(tr!(
+                not_in_scope_qualified,
+                symbol,
+                scope.unwrap(),
+                candidates.join(", ")
+            )));
+        }
+        Expression::Negate(operand) => Ok(Expression::Negate(Box::new(resolve_scoped_labels(
+            *operand,
+            scope,
+            symbol_table,
+        )?))),
+        Expression::Binary(operator, left, right) => Ok(Expression::Binary(
+            operator,
+            Box::new(resolve_scoped_labels(*left, scope, symbol_table)?),
+            Box::new(resolve_scoped_labels(*right, scope, symbol_table)?),
+        )),
+        expression => Ok(expression),
+    }
+}
+
+// addressの命令にある式を計算し、A命令で扱える15ビットの範囲に収まるか確認する
+fn evaluate(
+    expression: &Expression,
+    symbol_table: &SymbolTable,
+    local_labels: &LocalLabels,
+    address: u16,
+) -> Result<u16> {
+    let value = evaluate_value(expression, symbol_table, local_labels, address)?;
+    match u16::try_from(value) {
+        Ok(value) if value <= MAX_A_VALUE => Ok(value),
+        _ => bail!(ErrorCode::ValueOutOfRange.error(tr!(value_out_of_range, value, MAX_A_VALUE))),
+    }
+}
+
+// 範囲を確認せずに式を計算する
+fn evaluate_value(
+    expression: &Expression,
+    symbol_table: &SymbolTable,
+    local_labels: &LocalLabels,
+    address: u16,
+) -> Result<i64> {
+    expression.evaluate(&|term| {
+        match term {
+            Expression::Symbol(symbol) => symbol_table.address(symbol),
+            Expression::CurrentAddress => Some(address),
+            Expression::LocalLabel(label, direction) => {
+                local_labels.resolve(*label, *direction, address)
+            }
+            _ => None,
+        }
+        .map(i64::from)
+    })
+}
+
+// A命令で初めて使われた、宣言されていないシンボルに割り当てる変数
+struct ImplicitVariables<'a> {
+    ram: RamAllocator,
+    strict: bool,
+    warnings: &'a mut Vec<Warning>,
+    // 割り当てた変数ごとの、割り当て時の警告の位置(warningsの添字)
+    allocated: HashMap<String, usize>,
+}
+
+impl ImplicitVariables<'_> {
+    fn allocate(
+        &mut self,
+        symbol: &str,
+        symbol_table: &mut SymbolTable,
+        location: Location,
+    ) -> Result<u16> {
+        if self.strict {
+            let message = match symbol_table.similar_symbols(symbol).first() {
+                Some(similar) => tr!(undeclared_symbol_similar, symbol, similar),
+                None => tr!(undeclared_symbol, symbol),
+            };
+            return Err(anyhow!(ErrorCode::UndeclaredSymbol.error(message)))
+                .with_context(|| location);
+        }
+        let address = self.ram.allocate(symbol, 1)?;
+        symbol_table.add_variable(symbol, address, 1)?;
+        symbol_table.set_definition(symbol, location.clone());
+        self.allocated
+            .insert(symbol.to_string(), self.warnings.len());
+        self.warnings.push(Warning {
+            location,
+            message: tr!(implicit_variable, symbol, address),
+            rule: Rule::UndeclaredVariable,
+        });
+        Ok(address)
+    }
+
+    // 一度しか使われていない変数は綴り間違いの可能性が高いので、似た名前のシンボルを警告に添える
+    fn suggest(&mut self, symbol_table: &SymbolTable) {
+        let used_once = |symbol: &str| symbol_table.references(symbol).len() <= 1;
+        for (symbol, index) in self.allocated.iter() {
+            if !used_once(symbol) {
+                continue;
+            }
+            let similar = symbol_table
+                .similar_symbols(symbol)
+                .into_iter()
+                .find(|similar| !(self.allocated.contains_key(similar) && used_once(similar)));
+            if let Some(similar) = similar {
+                let message = &mut self.warnings[*index].message;
+                message.push_str(&tr!(used_once_similar, similar));
+            }
+        }
+    }
+}
+
+pub fn second_pass(
+    asm_parser: &mut Parser,
+    symbol_table: &mut SymbolTable,
+    definitions: &Definitions,
+    options: &Options,
+    warnings: &mut Vec<Warning>,
+) -> Result<Vec<u16>> {
+    let mut words = if options.init_prologue {
+        prologue::words(&definitions.ram_data())?
+    } else {
+        Vec::new()
+    };
+    let mut variables = ImplicitVariables {
+        ram: definitions.ram.clone(),
+        strict: options.strict,
+        warnings,
+        allocated: HashMap::new(),
+    };
+    let mut usage = Usage::default();
+    let mut scope: Option<String> = None;
+
+    while asm_parser.has_more_lines()? {
+        asm_parser.advance()?;
+        if asm_parser.instruction_type()? == Some(InstructionType::L) {
+            let symbol = asm_parser.symbol()?.unwrap();
+            if symbol.parse::<u32>().is_err() && opens_scope(&symbol) {
+                scope = Some(symbol);
+            }
+        }
+        let address = words.len() as u16;
+        let word = translate(
+            asm_parser,
+            symbol_table,
+            &definitions.local_labels,
+            address,
+            scope.as_deref(),
+            &mut variables,
+        )
+        .map_err(|error| with_instruction_location(error, asm_parser))?;
+        words.extend(word);
+        usage.record(asm_parser, symbol_table);
+
+        if !(asm_parser.has_more_lines()?) {
+            break;
+        }
+    }
+    variables.suggest(symbol_table);
+    let implicit: HashSet<&str> = variables.allocated.keys().map(String::as_str).collect();
+    variables
+        .warnings
+        .extend(usage.warnings(symbol_table, &implicit));
+
+    Ok(words)
+}
+
+// 命令の一部を指す位置が付いていなければ、命令全体の位置を付ける
+fn with_instruction_location(error: anyhow::Error, asm_parser: &Parser) -> anyhow::Error {
+    match error.downcast_ref::<Location>() {
+        Some(_) => error,
+        None => error.context(asm_parser.location()),
+    }
+}
+
+// 現在の命令を機械語に変換する。ラベルなど機械語を生成しない行はNoneを返す
+fn translate(
+    asm_parser: &Parser,
+    symbol_table: &mut SymbolTable,
+    local_labels: &LocalLabels,
+    address: u16,
+    scope: Option<&str>,
+    variables: &mut ImplicitVariables,
+) -> Result<Option<u16>> {
+    match asm_parser.instruction_type()? {
+        Some(InstructionType::A) => {
+            let expression =
+                resolve_scoped_labels(asm_parser.expression()?.unwrap(), scope, symbol_table)?;
+            //シンボル1つだけであれば変数として扱い、数値や式であれば計算した値を機械語とする
+            match expression {
+                //未定義のシンボルは変数として扱う
+                Expression::Symbol(symbol) => {
+                    if !symbol_table.contains(&symbol)? {
+                        let location = asm_parser.symbol_location(&symbol);
+                        variables.allocate(&symbol, symbol_table, location)?;
+                    }
+                    symbol_table.add_reference(&symbol, asm_parser.symbol_location(&symbol));
+                    //ROMの末尾のラベルなど、A命令で読み込めないアドレスもある
+                    match symbol_table.get_address(symbol.as_str())? {
+                        address if address <= MAX_A_VALUE => Ok(Some(address)),
+                        address => bail!(ErrorCode::ValueOutOfRange.error(tr!(
+                            value_out_of_range,
+                            address,
+                            MAX_A_VALUE
+                        ))),
+                    }
+                }
+                expression => {
+                    add_references(&expression, symbol_table, asm_parser);
+                    Ok(Some(evaluate(
+                        &expression,
+                        symbol_table,
+                        local_labels,
+                        address,
+                    )?))
+                }
+            }
+        }
+        Some(InstructionType::C) => {
+            //誤りのあるdest・comp・jumpの部分を指す位置を付ける
+            let dest =
+                code::Code::dest(asm_parser.dest()?).with_context(|| asm_parser.dest_location())?;
+            let comp =
+                code::Code::comp(asm_parser.comp()?).with_context(|| asm_parser.comp_location())?;
+            let jump =
+                code::Code::jump(asm_parser.jump()?).with_context(|| asm_parser.jump_location())?;
+            Ok(Some(c_word(&dest, &comp, &jump)?))
+        }
+        Some(InstructionType::L) | Some(InstructionType::Directive) => Ok(None),
+        None => Ok(None),
+    }
+}
+
+fn c_instruction(dest: &str, comp: &str, jump: &str) -> Result<u16> {
+    c_word(
+        &code::Code::dest(dest)?,
+        &code::Code::comp(comp)?,
+        &code::Code::jump(jump)?,
+    )
+}
+
+// 2進数の文字列にしたdest・comp・jumpを機械語にまとめる
+fn c_word(
+    dest_binary_string: &str,
+    comp_binary_string: &str,
+    jump_binary_string: &str,
+) -> Result<u16> {
+    let c_instruction_binary_string = format!(
+        "111{}{}{}",
+        comp_binary_string, dest_binary_string, jump_binary_string
+    );
+    Ok(u16::from_str_radix(&c_instruction_binary_string, 2)?)
+}
+
+// ラベルをアドレス順に返す
+pub fn label_entries(symbol_table: &SymbolTable) -> Vec<(String, u16)> {
+    symbol_table.symbols(SymbolKind::Label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Location, Span};
+
+    #[test]
+    fn test_first_pass() -> Result<()> {
+        let mut asm_parser =
+            Parser::from_source("(START)\n@40\n(LOOP)\nDM=A+1;JNE\n(STOP)\n(END)\n");
+        let mut symbol_table = SymbolTable::new();
+        first_pass(&mut asm_parser, &mut symbol_table, &Options::default())?;
+
+        assert_eq!(symbol_table.get_address("START")?, 0);
+        assert_eq!(symbol_table.get_address("LOOP")?, 1);
+        assert_eq!(symbol_table.get_address("STOP")?, 2);
+        assert_eq!(symbol_table.get_address("END")?, 2);
+        assert_eq!(
+            label_entries(&symbol_table),
+            vec![
+                ("START".to_string(), 0),
+                ("LOOP".to_string(), 1),
+                ("END".to_string(), 2),
+                ("STOP".to_string(), 2)
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_second_pass() -> Result<()> {
+        let source = "(START)\n@40\n(LOOP)\nDM=A+1;JNE\n(STOP)\nD=A\n(END)\n";
+        let mut symbol_table = SymbolTable::new();
+        let definitions = first_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &Options::default(),
+        )?;
+        second_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &definitions,
+            &Options::default(),
+            &mut Vec::new(),
+        )?;
+
+        assert_eq!(symbol_table.get_address("START")?, 0);
+        assert_eq!(symbol_table.get_address("LOOP")?, 1);
+        assert_eq!(symbol_table.get_address("STOP")?, 2);
+        assert_eq!(symbol_table.get_address("END")?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assemble_source() -> Result<()> {
+        let mut symbol_table = SymbolTable::new();
+        let words = assemble_source("@i\nM=1\n(LOOP)\n@LOOP\n0;JMP", &mut symbol_table)?;
+        assert_eq!(words, vec![16, 0xEFC8, 2, 0xEA87]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rom_size() {
+        let error = |source: &str| {
+            format!(
+                "{:#}",
+                assemble_source(source, &mut SymbolTable::new()).unwrap_err()
+            )
+        };
+        //ROMの末尾の直後を指すラベルはA命令で読み込めない
+        let source = format!("@END\n0;JMP\n{}(END)", "D=0\n".repeat(ROM_SIZE - 2));
+        assert_eq!(
+            error(&source),
+            "line 1: value 32768 is out of range (0 to 32767)"
+        );
+        let source = format!("{}(END)\n@END", "D=0\n".repeat(ROM_SIZE));
+        assert_eq!(
+            error(&source),
+            "line 32770: program does not fit in ROM (32769 words, at most 32768)"
+        );
+    }
+
+    #[test]
+    fn test_error_location() {
+        let error = assemble_source("@1\n\nD=X", &mut SymbolTable::new()).unwrap_err();
+        //誤りのあるcompだけを指す
+        assert_eq!(error.downcast_ref::<Location>(), Some(&at(3, 3, 4)));
+        assert_eq!(
+            format!("{:#}", error),
+            "line 3: unknown comp mnemonic: \"X\""
+        );
+
+        let location = |source: &str| {
+            let error = assemble_source(source, &mut SymbolTable::new()).unwrap_err();
+            error.downcast_ref::<Location>().cloned()
+        };
+        assert_eq!(location("  Q=D;JMP"), Some(at(1, 3, 4)));
+        assert_eq!(location("  D=M;JXX"), Some(at(1, 7, 10)));
+        //命令全体の誤りは、字下げを除いた命令全体を指す
+        assert_eq!(location("  @99999"), Some(at(1, 3, 9)));
+        assert_eq!(location("\t.var 1x"), Some(at(1, 2, 9)));
+    }
+
+    #[test]
+    fn test_expression() -> Result<()> {
+        let source = "\
+.equ ROW 32
+.equ ROW_END SCREEN+ROW-1
+@i
+@SCREEN+ROW*2
+@i+1
+(LOOP)
+@LOOP-1
+@ROW_END
+@(LOOP+8)<<2
+@END+1
+(END)";
+        let mut symbol_table = SymbolTable::new();
+        let words = assemble_source(source, &mut symbol_table)?;
+        assert_eq!(words, vec![16, 16448, 17, 2, 16415, 44, 8]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_literals() -> Result<()> {
+        let source = "@0x4000\nD=A\n@0b1010\n@'A'\n@KBD\nD=M\n@'q'\nD=D-A\n@0x10+'0'";
+        let words = assemble_source(source, &mut SymbolTable::new())?;
+        assert_eq!(
+            words,
+            vec![0x4000, 0xEC10, 10, 65, 24576, 0xFC10, 113, 0xE4D0, 64]
+        );
+
+        let error = assemble_source("@0xFFFF", &mut SymbolTable::new()).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "line 1: value 65535 is out of range (0 to 32767)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_labels() -> Result<()> {
+        let source = "\
+1:
+    @1f
+    0;JMP
+1:
+    @1b
+    D;JGT
+    @1b
+    0;JMP
+1:
+    @$
+    @$+2
+    @2f
+2:";
+        let mut symbol_table = SymbolTable::new();
+        let words = assemble_source(source, &mut symbol_table)?;
+        assert_eq!(words, vec![2, 0xEA87, 2, 0xE301, 2, 0xEA87, 6, 9, 9]);
+
+        //数値ローカルラベルはSymbolTableに登録しない
+        assert!(!symbol_table.contains("1")?);
+        assert!(label_entries(&symbol_table).is_empty());
+
+        let error = assemble_source("@1f\n1:\n@1f", &mut SymbolTable::new()).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "line 3: local label 1: not found after this instruction"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_scoped_labels() -> Result<()> {
+        let source = "\
+(MAIN)
+(.loop)
+    @.loop
+    0;JMP
+(SUB)
+.macro WAIT
+(W)
+    @W
+    0;JMP
+.endm
+    WAIT
+(.loop)
+    @.loop+1
+    @MAIN.loop
+    @.end
+(.end)";
+        let mut symbol_table = SymbolTable::new();
+        let words = assemble_source(source, &mut symbol_table)?;
+        assert_eq!(words, vec![0, 0xEA87, 2, 0xEA87, 5, 0, 7]);
+
+        //マクロ展開で付けられたラベルはスコープを変えない
+        assert_eq!(symbol_table.get_address("MAIN.loop")?, 0);
+        assert_eq!(symbol_table.get_address("SUB.loop")?, 4);
+        assert_eq!(symbol_table.get_address("SUB.end")?, 7);
+        assert!(!symbol_table.contains(".loop")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scoped_label_errors() {
+        let error = |source: &str| {
+            format!(
+                "{:#}",
+                assemble_source(source, &mut SymbolTable::new()).unwrap_err()
+            )
+        };
+        assert_eq!(
+            error("(.loop)"),
+            "line 1: local label .loop has no enclosing global label"
+        );
+        assert_eq!(
+            error("@.loop"),
+            "line 1: local label .loop has no enclosing global label"
+        );
+        assert_eq!(
+            error("(A)\n(.loop)\n(B)\n@.loop"),
+            "line 4: local label .loop is not defined in scope B (use the fully qualified name: A.loop)"
+        );
+        assert_eq!(
+            error("(A)\n@.end+1"),
+            "line 2: local label .end is not defined in scope A"
+        );
+    }
+
+    fn assemble_with_options(
+        source: &str,
+        options: &Options,
+    ) -> Result<(Vec<u16>, SymbolTable, Vec<Warning>)> {
+        let mut symbol_table = SymbolTable::new();
+        let mut warnings = Vec::new();
+        let definitions = first_pass(&mut Parser::from_source(source), &mut symbol_table, options)?;
+        let words = second_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &definitions,
+            options,
+            &mut warnings,
+        )?;
+        Ok((words, symbol_table, warnings))
+    }
+
+    // 行と、その中のトークンの範囲を指す位置
+    fn at(line: usize, start: usize, end: usize) -> Location {
+        Location {
+            span: Some(Span { start, end }),
+            ..Location::new(line)
+        }
+    }
+
+    #[test]
+    fn test_var() -> Result<()> {
+        let source = "@counter\nM=0\n.var counter\n.equ ROW 32\n.var buffer ROW*2\n@buffer\n@i\n@i";
+        let (words, symbol_table, warnings) = assemble_with_options(source, &Options::default())?;
+
+        //宣言した変数は使う位置より前にRAMを割り当て、未宣言の変数はその後ろに割り当てる
+        assert_eq!(words, vec![16, 0xEA88, 17, 81, 81]);
+        assert_eq!(symbol_table.kind("buffer"), Some(SymbolKind::Variable));
+        assert_eq!(
+            warnings,
+            vec![
+                Warning {
+                    location: at(7, 2, 3),
+                    message: "symbol i is not declared; allocated as a variable at RAM[81]"
+                        .to_string(),
+                    rule: Rule::UndeclaredVariable,
+                },
+                Warning {
+                    location: at(3, 6, 13),
+                    message: "variable counter is written but never read".to_string(),
+                    rule: Rule::WriteOnlyVariable,
+                },
+                Warning {
+                    location: at(5, 6, 12),
+                    message: "variable buffer is used only once".to_string(),
+                    rule: Rule::SingleUseVariable,
+                },
+            ]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "line 7: warning: symbol i is not declared; allocated as a variable at RAM[81]"
+        );
+
+        let strict = Options {
+            strict: true,
+            ..Default::default()
+        };
+        let (_, _, warnings) = assemble_with_options(".var i\n@i\n@i\n@R0\n(L)\n@L", &strict)?;
+        assert!(warnings.is_empty());
+        let error = assemble_with_options(".var counter\n@total", &strict)
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "line 2: undeclared symbol total (declare it with .var)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let strict = Options {
+            strict: true,
+            ..Default::default()
+        };
+        let cases = [
+            ("@32768", ErrorCode::ValueOutOfRange),
+            (".data t 70000", ErrorCode::ValueOutOfRange),
+            (".var i\n.var i", ErrorCode::DuplicateSymbol),
+            (".equ N 1\n.data N 2", ErrorCode::DuplicateSymbol),
+            (".var buf 4\n(buf)", ErrorCode::DuplicateSymbol),
+            ("(LOOP)\n@LOOP\n(LOOP)", ErrorCode::DuplicateSymbol),
+            ("(SCREEN)", ErrorCode::DuplicateSymbol),
+            (".equ 1ST 1", ErrorCode::InvalidSymbolName),
+            (".var D", ErrorCode::InvalidSymbolName),
+            (".var buffer 0", ErrorCode::InvalidSize),
+            (".record P x 0", ErrorCode::InvalidSize),
+            ("(.x)", ErrorCode::LabelOutOfScope),
+            ("(MAIN)\n@.x", ErrorCode::LabelOutOfScope),
+            (".org 5", ErrorCode::InvalidRamAddress),
+            (".var buffer 0x4000", ErrorCode::OutOfRam),
+            (".var a 4\n.org 17\n.var b", ErrorCode::VariableOverlap),
+        ];
+        for (source, code) in cases {
+            let error = assemble_with_options(source, &Options::default())
+                .err()
+                .unwrap();
+            assert_eq!(error_code::code_of(&error), Some(code), "{}", source);
+        }
+        let error = assemble_with_options(".var counter\n@coutner", &strict)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error_code::code_of(&error),
+            Some(ErrorCode::UndeclaredSymbol)
+        );
+    }
+
+    #[test]
+    fn test_ram_layout() -> Result<()> {
+        let source = "\
+.record Point x, y, color 2
+.var i
+.var points Point*4
+.org 0x1000
+.var table 16
+@points+Point.y
+@table
+@Point
+@j";
+        let mut symbol_table = SymbolTable::new();
+        let words = assemble_source(source, &mut symbol_table)?;
+        assert_eq!(words, vec![18, 0x1000, 4, 0x1010]);
+        assert_eq!(symbol_table.get_address("Point.color")?, 2);
+        assert_eq!(symbol_table.kind("Point.x"), Some(SymbolKind::Constant));
+        assert_eq!(symbol_table.size("points"), 16);
+
+        let error = |source: &str| {
+            format!(
+                "{:#}",
+                assemble_source(source, &mut SymbolTable::new()).unwrap_err()
+            )
+        };
+        assert_eq!(
+            error(".var a 4\n.org 18\n.var b"),
+            "line 3: variable b at RAM[18] overlaps variable a at RAM[16..20]"
+        );
+        assert_eq!(
+            error(".org 16384"),
+            "line 1: RAM address 16384 is out of range (0 to 16383)"
+        );
+        assert_eq!(
+            error(".org 0\n.var sp"),
+            "line 1: RAM address 0 is reserved (variables start at RAM[16])"
+        );
+        assert_eq!(
+            error(".record P x, x"),
+            "line 1: symbol P.x is already defined as a constant"
+        );
+        assert_eq!(
+            error(".record P x 0"),
+            "line 1: size of field x must be at least 1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_data() -> Result<()> {
+        let source = ".var i\n.data table 1, -1, 'A'+1\n.string msg \"Hi\"\n(START)\n@msg\n@START";
+        let mut symbol_table = SymbolTable::new();
+        let options = Options::default();
+        let definitions = first_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &options,
+        )?;
+        assert_eq!(
+            definitions.ram_data(),
+            vec![
+                (17, 1),
+                (18, 0xFFFF),
+                (19, 66),
+                (20, 72),
+                (21, 105),
+                (22, 0)
+            ]
+        );
+        assert_eq!(symbol_table.size("msg"), 3);
+
+        //初期化コードの後ろに利用者のコードを置き、ラベルもその分ずれる
+        let options = Options {
+            init_prologue: true,
+            ..Default::default()
+        };
+        let (words, symbol_table, _) = assemble_with_options(source, &options)?;
+        assert_eq!(symbol_table.get_address("START")?, 26);
+        assert_eq!(&words[..4], &[1, 0xEC10, 17, 0xE308]);
+        assert_eq!(&words[4..6], &[0, 0xEC50]);
+        assert_eq!(&words[24..], &[26, 0xEA87, 20, 26]);
+
+        //初期値がなければ初期化コードは置かない
+        let (words, _, _) = assemble_with_options("(START)\n@START", &options)?;
+        assert_eq!(words, vec![0]);
+
+        //初期化コードと利用者のコードがROMに収まらなければエラー
+        let values = vec!["1"; 9000].join(", ");
+        let source = format!(".data big {}\n(END)\n@END\n0;JMP", values);
+        assert_eq!(
+            format!(
+                "{:#}",
+                assemble_with_options(&source, &options).err().unwrap()
+            ),
+            "program does not fit in ROM (36002 words, at most 32768)"
+        );
+
+        let error = |source: &str| {
+            format!(
+                "{:#}",
+                assemble_source(source, &mut SymbolTable::new()).unwrap_err()
+            )
+        };
+        assert_eq!(
+            error(".data t 65536"),
+            "line 1: value 65536 is out of range (-32768 to 65535)"
+        );
+        assert_eq!(
+            error(".var t\n.string t \"x\""),
+            "line 2: symbol t is already defined as a variable"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_variable_base() -> Result<()> {
+        let options = Options {
+            variable_base: 0x100,
+            ..Default::default()
+        };
+        let (words, _, _) = assemble_with_options(".var a 2\n@a\n@b", &options)?;
+        assert_eq!(words, vec![0x100, 0x102]);
+
+        // 16番地より前から割り当てる設定では、.orgもその番地まで戻れる
+        let options = Options {
+            variable_base: 8,
+            ..Default::default()
+        };
+        let (words, _, _) = assemble_with_options(".org 12\n.var a\n@a", &options)?;
+        assert_eq!(words, vec![12]);
+        assert!(assemble_with_options(".org 7", &options).is_err());
+
+        let options = Options {
+            variable_base: 0x4000,
+            ..Default::default()
+        };
+        let error = assemble_with_options("@a", &options).err().unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "invalid variable base address: RAM address 16384 is out of range (0 to 16383)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_did_you_mean() -> Result<()> {
+        let source =
+            "(LOOP)\n@loop\n0;JMP\n.var counter\n@coutner\nM=0\n@counter\n@tmp\n@tmp\n@tmp2\n@i";
+        let (_, _, warnings) = assemble_with_options(source, &Options::default())?;
+        let messages: Vec<&str> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "symbol loop is not declared; allocated as a variable at RAM[17] (used only once; did you mean LOOP?)",
+                "symbol coutner is not declared; allocated as a variable at RAM[18] (used only once; did you mean counter?)",
+                "symbol tmp is not declared; allocated as a variable at RAM[19]",
+                "symbol tmp2 is not declared; allocated as a variable at RAM[20] (used only once; did you mean tmp?)",
+                "symbol i is not declared; allocated as a variable at RAM[21]",
+                "label LOOP is never referenced",
+                "variable counter is used only once",
+                "variable coutner is written but never read",
+            ]
+        );
+
+        let strict = Options {
+            strict: true,
+            ..Default::default()
+        };
+        let error = assemble_with_options("(LOOP)\n@loop", &strict)
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "line 2: undeclared symbol loop (did you mean LOOP?)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbol_info() -> Result<()> {
+        let source = ".equ ROW 32
+.var buffer ROW*2
+(LOOP)
+@buffer
+@i
+M=0
+@LOOP+1
+0;JMP";
+        let (_, symbol_table, _) = assemble_with_options(source, &Options::default())?;
+
+        let buffer = symbol_table.get("buffer").unwrap();
+        assert_eq!(buffer.kind, SymbolKind::Variable);
+        assert_eq!((buffer.address, buffer.size), (16, 64));
+        assert_eq!(buffer.definition, Some(at(2, 6, 12)));
+        assert_eq!(buffer.references, vec![at(4, 2, 8)]);
+        //式やディレクティブの引数の中のシンボルも参照として記録する
+        assert_eq!(symbol_table.references("ROW"), &[at(2, 13, 16)]);
+        assert_eq!(symbol_table.references("LOOP"), &[at(7, 2, 6)]);
+        //未宣言の変数は最初に使われた位置を定義とする
+        assert_eq!(symbol_table.get("i").unwrap().definition, Some(at(5, 2, 3)));
+        assert_eq!(symbol_table.get("SCREEN").unwrap().definition, None);
+
+        //定義済みシンボルの後ろに、定義した順に並ぶ
+        let names: Vec<&str> = symbol_table
+            .iter()
+            .filter(|info| info.definition.is_some())
+            .map(|info| info.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["ROW", "buffer", "LOOP", "i"]);
+
+        //字下げした行やスコープ付きのラベルは、書かれたトークンの範囲を指す
+        let source = "(Main)\n  (.loop)\n  @.loop\n  0;JMP\n.record P x, y\n@P.y";
+        let (_, symbol_table, _) = assemble_with_options(source, &Options::default())?;
+        let label = symbol_table.get("Main.loop").unwrap();
+        assert_eq!(label.definition, Some(at(2, 4, 9)));
+        assert_eq!(label.references, vec![at(3, 4, 9)]);
+        assert_eq!(
+            symbol_table.get("P.y").unwrap().definition,
+            Some(at(5, 14, 15))
+        );
+        assert_eq!(symbol_table.references("P.y"), &[at(6, 2, 5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_var_errors() {
+        let error = |source: &str| {
+            format!(
+                "{:#}",
+                assemble_source(source, &mut SymbolTable::new()).unwrap_err()
+            )
+        };
+        assert_eq!(
+            error(".var i\n.var i"),
+            "line 2: symbol i is already defined as a variable"
+        );
+        assert_eq!(
+            error(".var SCREEN"),
+            "line 1: symbol SCREEN is already defined as a predefined symbol"
+        );
+        assert_eq!(
+            error(".var buffer 0"),
+            "line 1: size of variable buffer must be at least 1"
+        );
+        assert_eq!(
+            error(".var buffer 16368\n@i"),
+            "line 2: not enough RAM for variable i (1 word(s) from RAM[16384])"
+        );
+        assert_eq!(error(".var 1x"), "line 1: invalid variable name: \"1x\"");
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        let error = |source: &str| {
+            format!(
+                "{:#}",
+                assemble_source(source, &mut SymbolTable::new()).unwrap_err()
+            )
+        };
+        assert_eq!(error("@i+1"), "line 1: undefined symbol i in expression");
+        assert_eq!(
+            error("@32768"),
+            "line 1: value 32768 is out of range (0 to 32767)"
+        );
+        assert_eq!(
+            error("@0-1"),
+            "line 1: value -1 is out of range (0 to 32767)"
+        );
+        assert_eq!(
+            error(".equ N LATER\n(LATER)"),
+            "line 1: undefined symbol LATER in expression"
+        );
+    }
+
+    #[test]
+    fn test_macro_error_location() {
+        let source = ".macro PUSH value\n@%value\nD=X\n.endm\nPUSH 1";
+        let error = assemble_source(source, &mut SymbolTable::new()).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "line 3 (in expansion of macro PUSH at line 5): unknown comp mnemonic: \"X\""
+        );
+    }
+
+    #[test]
+    fn test_constant() -> Result<()> {
+        let source = "@WIDTH\nD=A\n.equ WIDTH 512\n.define HEIGHT 256\n@HEIGHT\n@i";
+        let mut symbol_table = SymbolTable::new();
+        let words = assemble_source(source, &mut symbol_table)?;
+
+        //定数はRAMを割り当てないので変数iは16番地になる
+        assert_eq!(words, vec![512, 0xEC10, 256, 16]);
+        assert_eq!(symbol_table.kind("WIDTH"), Some(SymbolKind::Constant));
+        assert_eq!(symbol_table.kind("i"), Some(SymbolKind::Variable));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_constant_errors() {
+        let error = |source: &str| {
+            format!(
+                "{:#}",
+                assemble_source(source, &mut SymbolTable::new()).unwrap_err()
+            )
+        };
+        assert_eq!(
+            error(".equ N 1\n.equ N 2"),
+            "line 2: symbol N is already defined as a constant"
+        );
+        assert_eq!(
+            error("(N)\n.equ N 2"),
+            "line 2: symbol N is already defined as a label"
+        );
+        assert_eq!(
+            error(".equ N 2\n(N)"),
+            "line 2: symbol N is already defined as a constant"
+        );
+        assert_eq!(
+            error(".equ N 32768"),
+            "line 1: value 32768 is out of range (0 to 32767)"
+        );
+        assert_eq!(error(".equ 1N 1"), "line 1: invalid constant name: \"1N\"");
+        assert_eq!(error(".equ M 1"), "line 1: invalid constant name: \"M\"");
+    }
+}
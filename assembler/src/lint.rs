@@ -0,0 +1,450 @@
+//! 誤りの可能性が高い命令の並びを見つける`--lint`の検査
+//!
+//! アセンブルとは別に、前処理を済ませた命令を読み直して調べる。
+
+use crate::{evaluate, rules::Rule, Definitions, Warning};
+use anyhow::Result;
+use message::tr;
+use parser::{
+    expression::{self, Expression},
+    InstructionType, Location, Parser,
+};
+use std::{collections::HashSet, fmt};
+use symbol_table::{SymbolKind, SymbolTable};
+
+// 検査する命令
+#[derive(Debug, PartialEq)]
+enum Instruction {
+    A(String),
+    C {
+        dest: String,
+        comp: String,
+        jump: String,
+    },
+    Label(String),
+    // ディレクティブなど、機械語にならない行
+    Other,
+}
+
+// 前処理を済ませた行を読み、検査する命令とその位置の並びにする
+fn instructions(asm_parser: &mut Parser) -> Result<Vec<(Location, Instruction)>> {
+    let mut instructions = Vec::new();
+    while asm_parser.has_more_lines()? {
+        asm_parser.advance()?;
+        let instruction = match asm_parser.instruction_type()? {
+            Some(InstructionType::A) => Instruction::A(asm_parser.symbol()?.unwrap()),
+            Some(InstructionType::C) => Instruction::C {
+                dest: asm_parser.dest()?.to_string(),
+                comp: asm_parser.comp()?.to_string(),
+                jump: asm_parser.jump()?.to_string(),
+            },
+            Some(InstructionType::L) => Instruction::Label(asm_parser.symbol()?.unwrap()),
+            Some(InstructionType::Directive) | None => Instruction::Other,
+        };
+        instructions.push((asm_parser.location(), instruction));
+    }
+    Ok(instructions)
+}
+
+// 分岐やラベルで区切った、先頭から順に実行される命令の並び
+#[derive(Debug)]
+struct Block {
+    // 先頭の命令のROMアドレスと位置
+    address: u16,
+    location: Option<Location>,
+    // 命令数
+    size: u16,
+    // 参照されたラベルから始まり、ジャンプで入ってくる可能性がある
+    entry: bool,
+    // 最後の命令が常にジャンプするものでなく、次のブロックへ進む
+    falls_through: bool,
+}
+
+impl Block {
+    fn new(address: u16, entry: bool) -> Self {
+        Self {
+            address,
+            location: None,
+            size: 0,
+            entry,
+            falls_through: true,
+        }
+    }
+}
+
+/// 前処理を済ませた行を検査し、警告を返す。symbol_tableとdefinitionsはアセンブルを終えたもの
+pub fn lint(
+    asm_parser: &mut Parser,
+    symbol_table: &SymbolTable,
+    definitions: &Definitions,
+) -> Result<Vec<Warning>> {
+    let instructions = instructions(asm_parser)?;
+    let mut warnings = Vec::new();
+    for (location, instruction) in instructions.iter() {
+        warnings.extend(
+            jump_with_memory(instruction)
+                .into_iter()
+                .map(|(rule, message)| Warning {
+                    location: location.clone(),
+                    message,
+                    rule,
+                }),
+        );
+    }
+    warnings.extend(jump_to_data(&instructions, symbol_table));
+    let blocks = basic_blocks(&instructions, symbol_table, definitions);
+    warnings.extend(unreachable_code(&blocks));
+    Ok(warnings)
+}
+
+// ジャンプ先のAに、ラベルではなく変数や定数のアドレスを読み込んだままになっているものを警告する
+fn jump_to_data(
+    instructions: &[(Location, Instruction)],
+    symbol_table: &SymbolTable,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    // 直前のA命令で読み込んだラベル以外のシンボルとその種別。Aの値が分からなければNone
+    let mut loaded: Option<(&str, SymbolKind)> = None;
+    for (location, instruction) in instructions.iter() {
+        match instruction {
+            Instruction::A(value) => {
+                loaded = symbol_table
+                    .kind(value)
+                    .filter(|kind| *kind != SymbolKind::Label)
+                    .map(|kind| (value.as_str(), kind));
+            }
+            Instruction::C { dest, jump, .. } => {
+                if let (Some((symbol, kind)), false) = (loaded, jump.is_empty()) {
+                    warnings.push(Warning {
+                        location: location.clone(),
+                        message: tr!(jump_to_data, instruction, symbol, kind.description()),
+                        rule: Rule::JumpToData,
+                    });
+                }
+                if dest.contains('A') {
+                    loaded = None;
+                }
+            }
+            //ラベルには他の場所からジャンプしてくるのでAの値は分からない
+            Instruction::Label(_) => loaded = None,
+            Instruction::Other => (),
+        }
+    }
+    warnings
+}
+
+// ラベルを使わずにAへ読み込むROMアドレス。`@$+3`や`.equ`で名前を付けたアドレスへのジャンプを
+// 見落とさないよう、数値と変数以外の式の値はすべてジャンプ先になりうるとみなす
+fn computed_targets(
+    instructions: &[(Location, Instruction)],
+    symbol_table: &SymbolTable,
+    definitions: &Definitions,
+) -> HashSet<u16> {
+    let mut targets = HashSet::new();
+    let mut address = definitions.code_start;
+    for (_, instruction) in instructions.iter() {
+        match instruction {
+            Instruction::A(value) => {
+                let target = expression::parse(value)
+                    .ok()
+                    .filter(|expression| match expression {
+                        Expression::Number(_) => false,
+                        Expression::Symbol(symbol) => {
+                            symbol_table.kind(symbol) == Some(SymbolKind::Constant)
+                        }
+                        _ => true,
+                    })
+                    .and_then(|expression| {
+                        evaluate(
+                            &expression,
+                            symbol_table,
+                            &definitions.local_labels,
+                            address,
+                        )
+                        .ok()
+                    });
+                targets.extend(target);
+                address += 1;
+            }
+            Instruction::C { .. } => address += 1,
+            Instruction::Label(_) | Instruction::Other => (),
+        }
+    }
+    targets
+}
+
+// 命令の並びを基本ブロックに分ける
+fn basic_blocks(
+    instructions: &[(Location, Instruction)],
+    symbol_table: &SymbolTable,
+    definitions: &Definitions,
+) -> Vec<Block> {
+    // どこかで参照されたラベルのアドレスと、式で求めたジャンプ先になりうるアドレス
+    let mut referenced: HashSet<u16> = symbol_table
+        .iter()
+        .filter(|info| info.kind == SymbolKind::Label && !info.references.is_empty())
+        .map(|info| info.address)
+        .collect();
+    referenced.extend(computed_targets(instructions, symbol_table, definitions));
+    let code_start = definitions.code_start;
+    let mut blocks = vec![Block::new(code_start, true)];
+    let mut address = code_start;
+    for (location, instruction) in instructions.iter() {
+        let block = blocks.last_mut().unwrap();
+        match instruction {
+            Instruction::Label(label) => {
+                //数字のローカルラベルは近くから参照されるものとみなす
+                let entry = label.parse::<u32>().is_ok() || referenced.contains(&address);
+                if block.size == 0 {
+                    block.entry |= entry;
+                } else {
+                    blocks.push(Block::new(address, entry));
+                }
+            }
+            Instruction::A(_) | Instruction::C { .. } => {
+                //ラベルのない命令にも、式で求めたアドレスからジャンプしてくることがある
+                if referenced.contains(&address) {
+                    if block.size == 0 {
+                        block.entry = true;
+                    } else {
+                        blocks.push(Block::new(address, true));
+                    }
+                }
+                let block = blocks.last_mut().unwrap();
+                block.location.get_or_insert_with(|| location.clone());
+                block.size += 1;
+                address += 1;
+                if let Instruction::C { comp, jump, .. } = instruction {
+                    if !jump.is_empty() {
+                        block.falls_through = !always_jumps(comp, jump);
+                        blocks.push(Block::new(address, false));
+                    }
+                }
+            }
+            Instruction::Other => (),
+        }
+    }
+    blocks
+}
+
+// compが定数で、ジャンプの条件が常に成り立つか
+fn always_jumps(comp: &str, jump: &str) -> bool {
+    let value = match comp {
+        "0" => 0,
+        "1" => 1,
+        "-1" => -1,
+        _ => return jump == "JMP",
+    };
+    match jump {
+        "JGT" => value > 0,
+        "JEQ" => value == 0,
+        "JGE" => value >= 0,
+        "JLT" => value < 0,
+        "JNE" => value != 0,
+        "JLE" => value <= 0,
+        "JMP" => true,
+        _ => false,
+    }
+}
+
+// 先頭からも参照されたラベルからもたどり着けない命令の範囲を警告する
+fn unreachable_code(blocks: &[Block]) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    // 到達できない命令の範囲(先頭のアドレスと位置)
+    let mut unreachable: Option<(u16, Location)> = None;
+    let mut previous_reachable = false;
+    let mut previous_falls_through = false;
+    for block in blocks.iter() {
+        let reachable = block.entry || (previous_reachable && previous_falls_through);
+        if reachable || block.size > 0 {
+            previous_reachable = reachable;
+            previous_falls_through = block.falls_through;
+        }
+        match (&unreachable, reachable) {
+            (None, false) if block.size > 0 => {
+                unreachable = Some((block.address, block.location.clone().unwrap()));
+            }
+            (Some((start, location)), true) => {
+                warnings.push(unreachable_warning(*start, block.address, location));
+                unreachable = None;
+            }
+            _ => (),
+        }
+    }
+    if let (Some((start, location)), Some(last)) = (&unreachable, blocks.last()) {
+        warnings.push(unreachable_warning(
+            *start,
+            last.address + last.size,
+            location,
+        ));
+    }
+    warnings
+}
+
+fn unreachable_warning(start: u16, end: u16, location: &Location) -> Warning {
+    Warning {
+        location: location.clone(),
+        message: tr!(unreachable_code, start, end),
+        rule: Rule::UnreachableCode,
+    }
+}
+
+// ジャンプする命令ではAがジャンプ先を表すので、同じ命令でMを使ったりAに書き込んだりしない
+fn jump_with_memory(instruction: &Instruction) -> Vec<(Rule, String)> {
+    let Instruction::C { dest, comp, jump } = instruction else {
+        return Vec::new();
+    };
+    if jump.is_empty() {
+        return Vec::new();
+    }
+    let mut messages = Vec::new();
+    if dest.contains('M') || comp.contains('M') {
+        messages.push((Rule::MemoryInJump, tr!(memory_in_jump, instruction)));
+    }
+    if dest.contains('A') {
+        messages.push((Rule::AWriteInJump, tr!(a_write_in_jump, instruction)));
+    }
+    messages
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::A(value) => write!(f, "@{}", value),
+            Instruction::C { dest, comp, jump } => {
+                if !dest.is_empty() {
+                    write!(f, "{}=", dest)?;
+                }
+                write!(f, "{}", comp)?;
+                if !jump.is_empty() {
+                    write!(f, ";{}", jump)?;
+                }
+                Ok(())
+            }
+            Instruction::Label(label) => write!(f, "({})", label),
+            Instruction::Other => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{first_pass, second_pass, Options};
+
+    fn lint_warnings(source: &str) -> Result<Vec<Warning>> {
+        let mut symbol_table = SymbolTable::new();
+        let options = Options::default();
+        let definitions = first_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &options,
+        )?;
+        second_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &definitions,
+            &options,
+            &mut Vec::new(),
+        )?;
+        lint(
+            &mut Parser::from_source(source),
+            &symbol_table,
+            &definitions,
+        )
+    }
+
+    fn lint_messages(source: &str) -> Result<Vec<String>> {
+        Ok(lint_warnings(source)?
+            .iter()
+            .map(|w| w.to_string())
+            .collect())
+    }
+
+    // 指定した規則の警告だけを返す
+    fn rule_messages(source: &str, rules: &[Rule]) -> Result<Vec<String>> {
+        Ok(lint_warnings(source)?
+            .iter()
+            .filter(|w| rules.contains(&w.rule))
+            .map(|w| w.to_string())
+            .collect())
+    }
+
+    const JUMP_SOURCE: &str =
+        "@LOOP\nD;JGT\nM=D\n@LOOP\nD=M;JEQ\nM;JMP\nAM=M-1;JNE\nA=D;JMP\n(LOOP)";
+
+    #[test]
+    fn test_jump_with_memory() -> Result<()> {
+        assert_eq!(
+            rule_messages(JUMP_SOURCE, &[Rule::MemoryInJump, Rule::AWriteInJump])?,
+            vec![
+                "line 5: warning: D=M;JEQ uses M in a jumping instruction; A is both the jump target and the memory address",
+                "line 6: warning: M;JMP uses M in a jumping instruction; A is both the jump target and the memory address",
+                "line 7: warning: AM=M-1;JNE uses M in a jumping instruction; A is both the jump target and the memory address",
+                "line 7: warning: AM=M-1;JNE writes A in a jumping instruction; the jump uses A before the write",
+                "line 8: warning: A=D;JMP writes A in a jumping instruction; the jump uses A before the write",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_rules() -> Result<()> {
+        //M;JMPの後ろの命令はどのラベルからも到達できない
+        assert_eq!(
+            lint_messages(JUMP_SOURCE)?,
+            vec![
+                "line 5: warning: D=M;JEQ uses M in a jumping instruction; A is both the jump target and the memory address",
+                "line 6: warning: M;JMP uses M in a jumping instruction; A is both the jump target and the memory address",
+                "line 7: warning: AM=M-1;JNE uses M in a jumping instruction; A is both the jump target and the memory address",
+                "line 7: warning: AM=M-1;JNE writes A in a jumping instruction; the jump uses A before the write",
+                "line 8: warning: A=D;JMP writes A in a jumping instruction; the jump uses A before the write",
+                "line 7: warning: unreachable code at ROM[6..8] after an unconditional jump (missing label?)",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_jump_to_data() -> Result<()> {
+        let source = ".equ LIMIT 10\n.var x\n@x\nD=M\nD;JGT\n@LIMIT\nD;JEQ\n\
+                      @R13\nA=M\n0;JMP\n(LOOP)\n@x\nD=M\n@LOOP\nD;JNE\n@100\nD;JLT\n@x\nM=D";
+        assert_eq!(
+            lint_messages(source)?,
+            vec![
+                "line 5: warning: D;JGT jumps to x (variable) instead of a label",
+                "line 7: warning: D;JEQ jumps to LIMIT (constant) instead of a label",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreachable_code() -> Result<()> {
+        let source = "@END\n0;JMP\nD=1\nD=D+1\n(LOOP)\n@LOOP\nD;JGT\nD=0\n\
+                      (END)\n@END\n0;JEQ\n(UNUSED)\nM=1\n.var x\n(_last)";
+        assert_eq!(
+            lint_messages(source)?,
+            vec![
+                "line 3: warning: unreachable code at ROM[2..4] after an unconditional jump (missing label?)",
+                "line 13: warning: unreachable code at ROM[9..10] after an unconditional jump (missing label?)",
+            ]
+        );
+
+        //条件付きのジャンプの後ろや、ラベルから始まるコードは警告しない
+        let source = "@1f\n0;JMP\n1:\n@i\nD=M\n@1b\nD;JNE\nD=0\n(LOOP)\n@LOOP\n0;JMP";
+        assert!(lint_messages(source)?.is_empty());
+
+        //`$`からの相対位置や`.equ`で名前を付けたアドレスへのジャンプは、ラベルがなくても到達できる
+        let source = "@$+3\n0;JMP\nD=1\nD=D+1\n.equ RETRY 7\n@RETRY\n0;JMP\nD=0\nM=D";
+        assert_eq!(
+            rule_messages(source, &[Rule::UnreachableCode])?,
+            vec![
+                "line 3: warning: unreachable code at ROM[2..3] after an unconditional jump (missing label?)",
+                "line 8: warning: unreachable code at ROM[6..7] after an unconditional jump (missing label?)",
+            ]
+        );
+        Ok(())
+    }
+}
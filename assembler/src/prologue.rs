@@ -0,0 +1,75 @@
+//! `.data`/`.string`で宣言したRAMの初期値を書き込む初期化コード
+//!
+//! 0番地に置き、各ワードを`@値 D=A @アドレス M=D`で書き込んだあと利用者のコードの先頭へジャンプする。
+//! 値によらず1ワードあたりの命令数を一定にしているので、first_passの前に大きさが決まる。
+//! 初期値がなければ初期化コードは置かない。
+
+use crate::{c_instruction, MAX_A_VALUE, ROM_SIZE};
+use anyhow::{bail, Result};
+use message::tr;
+
+// 初期値1ワードを書き込む命令数
+const WORDS_PER_VALUE: usize = 4;
+// 最後のジャンプの命令数
+const JUMP_WORDS: usize = 2;
+
+// data_wordsワードを初期化するコードの命令数
+pub fn size(data_words: usize) -> usize {
+    match data_words {
+        0 => 0,
+        _ => data_words * WORDS_PER_VALUE + JUMP_WORDS,
+    }
+}
+
+// (アドレス, 値)の並びを書き込み、初期化コードの直後へジャンプする機械語を返す
+pub fn words(ram_data: &[(u16, u16)]) -> Result<Vec<u16>> {
+    let mut words = Vec::new();
+    if ram_data.is_empty() {
+        return Ok(words);
+    }
+    //ジャンプ先(初期化コードの直後)もA命令で読み込める範囲でなければならない
+    let size = size(ram_data.len());
+    let code_start = match u16::try_from(size) {
+        Ok(size) if size <= MAX_A_VALUE => size,
+        _ => bail!(tr!(rom_overflow, size, ROM_SIZE)),
+    };
+    for (address, value) in ram_data {
+        // A命令で扱えない値は反転した値から作る
+        if *value <= MAX_A_VALUE {
+            words.push(*value);
+            words.push(c_instruction("D", "A", "")?);
+        } else {
+            words.push(!*value);
+            words.push(c_instruction("D", "!A", "")?);
+        }
+        words.push(*address);
+        words.push(c_instruction("M", "D", "")?);
+    }
+    words.push(code_start);
+    words.push(c_instruction("", "0", "JMP")?);
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words() -> Result<()> {
+        assert_eq!(words(&[])?, vec![]);
+        assert_eq!(size(0), 0);
+        assert_eq!(
+            words(&[(16, 65), (17, 0xFFFF)])?,
+            vec![65, 0xEC10, 16, 0xE308, 0, 0xEC50, 17, 0xE308, 10, 0xEA87]
+        );
+        assert_eq!(size(2), 10);
+
+        let ram_data: Vec<(u16, u16)> = (0..9000).map(|i| (16 + i, i)).collect();
+        assert_eq!(
+            words(&ram_data).unwrap_err().to_string(),
+            "program does not fit in ROM (36002 words, at most 32768)"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! 変数へのRAMの割り当て
+//!
+//! `.var`で宣言した変数とA命令で初めて使われた変数に、16番地から順にRAMを割り当てる。
+//! `.org`で以降の割り当てを始めるアドレスを変えられるので、割り当てた領域が重ならないか確認する。
+//! R0〜R15(SP、LCLなど)を変数で上書きしないよう、`.org`で16番地より前には戻れない。
+
+use anyhow::{bail, Result};
+use error_code::ErrorCode;
+use message::tr;
+
+// 変数を割り当てるRAMの範囲(スクリーンの手前まで)
+pub const VARIABLE_RAM_START: u16 = 16;
+pub const VARIABLE_RAM_END: u16 = 0x4000;
+
+#[derive(Debug, Clone)]
+pub struct RamAllocator {
+    // 変数を割り当てられる最も小さいアドレス
+    start: u16,
+    // 次に割り当てるアドレス
+    next: u16,
+    // 割り当て済みの領域(先頭アドレス、ワード数、変数名)
+    allocations: Vec<(u16, u16, String)>,
+}
+
+impl RamAllocator {
+    pub fn new(start: u16) -> Self {
+        Self {
+            start,
+            next: start,
+            allocations: Vec::new(),
+        }
+    }
+
+    // 以降の割り当てをaddressから始める
+    pub fn set_origin(&mut self, address: u16) -> Result<()> {
+        if address >= VARIABLE_RAM_END {
+            bail!(ErrorCode::InvalidRamAddress.error(tr!(
+                ram_address_out_of_range,
+                address,
+                VARIABLE_RAM_END - 1
+            )));
+        }
+        if address < self.start {
+            bail!(ErrorCode::InvalidRamAddress.error(tr!(
+                ram_address_reserved,
+                address,
+                self.start
+            )));
+        }
+        self.next = address;
+        Ok(())
+    }
+
+    // sizeワードの変数にRAMを割り当て、先頭アドレスを返す
+    pub fn allocate(&mut self, name: &str, size: u16) -> Result<u16> {
+        if size == 0 {
+            bail!(ErrorCode::InvalidSize.error(tr!(variable_size, name)));
+        }
+        let address = self.next;
+        let end = match address.checked_add(size) {
+            Some(end) if end <= VARIABLE_RAM_END => end,
+            _ => bail!(ErrorCode::OutOfRam.error(tr!(not_enough_ram, name, size, address))),
+        };
+        if let Some((start, other_size, other)) = self
+            .allocations
+            .iter()
+            .find(|(start, other_size, _)| address < start + other_size && *start < end)
+        {
+            bail!(ErrorCode::VariableOverlap.error(tr!(
+                variable_overlaps,
+                name,
+                address,
+                other,
+                start,
+                start + other_size
+            )));
+        }
+        self.allocations.push((address, size, name.to_string()));
+        self.next = end;
+        Ok(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate() -> Result<()> {
+        let mut ram = RamAllocator::new(VARIABLE_RAM_START);
+        assert_eq!(ram.allocate("i", 1)?, 16);
+        assert_eq!(ram.allocate("buffer", 64)?, 17);
+        ram.set_origin(1000)?;
+        assert_eq!(ram.allocate("table", 10)?, 1000);
+        assert_eq!(ram.allocate("j", 1)?, 1010);
+
+        ram.set_origin(80)?;
+        assert_eq!(
+            ram.allocate("k", 2).unwrap_err().to_string(),
+            "variable k at RAM[80] overlaps variable buffer at RAM[17..81]"
+        );
+        assert!(ram.allocate("empty", 0).is_err());
+        assert!(ram.set_origin(VARIABLE_RAM_END).is_err());
+        assert_eq!(
+            ram.set_origin(15).unwrap_err().to_string(),
+            "RAM address 15 is reserved (variables start at RAM[16])"
+        );
+
+        ram.set_origin(VARIABLE_RAM_END - 1)?;
+        assert_eq!(ram.allocate("last", 1)?, VARIABLE_RAM_END - 1);
+        assert!(ram.allocate("over", 1).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+//! 警告の規則と、規則ごとの水準(allow/warn/deny)の設定
+//!
+//! 水準は規則の既定値、`--lint`、設定ファイル、コマンドラインの順に上書きし、
+//! 最後にソース中の`// hackasm:allow(unused-label)`のようなプラグマで次の行だけ変えられる。
+//! 次の行がマクロの呼び出しであれば、その展開で生成された行すべてに適用する。
+
+use crate::Warning;
+use anyhow::{anyhow, bail, Context, Result};
+use message::tr;
+use parser::{Location, SourceLine};
+use std::{collections::HashMap, fmt, iter, str::FromStr};
+
+// プラグマのコメントの書き出し
+const PRAGMA_PREFIX: &str = "hackasm:";
+const COMMENT_TOKEN: &str = "//";
+const RULE_SEPARATOR: char = ',';
+
+/// 警告の水準
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// 警告しない
+    Allow,
+    /// 警告する
+    Warn,
+    /// エラーにしてアセンブルを失敗させる
+    Deny,
+}
+
+impl FromStr for Level {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "allow" => Ok(Level::Allow),
+            "warn" => Ok(Level::Warn),
+            "deny" => Ok(Level::Deny),
+            _ => bail!(tr!(unknown_lint_level, s)),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        };
+        f.pad(level)
+    }
+}
+
+/// 警告の規則
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// 宣言していないシンボルを変数として割り当てた
+    UndeclaredVariable,
+    /// どこからも参照されないラベル
+    UnusedLabel,
+    /// 宣言したが使われない変数
+    UnusedVariable,
+    /// 1回しか使われない変数
+    SingleUseVariable,
+    /// `M=`で書き込むだけで読まれない変数
+    WriteOnlyVariable,
+    /// ジャンプする命令でのMの使用
+    MemoryInJump,
+    /// ジャンプする命令でのAへの書き込み
+    AWriteInJump,
+    /// 常にジャンプする命令の後ろの到達できないコード
+    UnreachableCode,
+    /// 変数や定数を読み込んだAへのジャンプ
+    JumpToData,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 9] = [
+        Rule::UndeclaredVariable,
+        Rule::UnusedLabel,
+        Rule::UnusedVariable,
+        Rule::SingleUseVariable,
+        Rule::WriteOnlyVariable,
+        Rule::MemoryInJump,
+        Rule::AWriteInJump,
+        Rule::UnreachableCode,
+        Rule::JumpToData,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::UndeclaredVariable => "undeclared-variable",
+            Rule::UnusedLabel => "unused-label",
+            Rule::UnusedVariable => "unused-variable",
+            Rule::SingleUseVariable => "single-use-variable",
+            Rule::WriteOnlyVariable => "write-only-variable",
+            Rule::MemoryInJump => "memory-in-jump",
+            Rule::AWriteInJump => "a-write-in-jump",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::JumpToData => "jump-to-data",
+        }
+    }
+
+    // 命令の並びを調べるリントの規則は`--lint`で有効にするまで警告しない
+    pub fn default_level(&self) -> Level {
+        match self {
+            Rule::MemoryInJump | Rule::AWriteInJump | Rule::UnreachableCode | Rule::JumpToData => {
+                Level::Allow
+            }
+            _ => Level::Warn,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.id() == s)
+            .ok_or_else(|| anyhow!(tr!(unknown_lint_rule, s)))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.id())
+    }
+}
+
+/// 規則ごとの水準の設定
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Rule, Level>,
+    /// warnの規則をすべてdenyとして扱う
+    pub deny_warnings: bool,
+}
+
+impl LintLevels {
+    pub fn set(&mut self, rule: Rule, level: Level) {
+        self.levels.insert(rule, level);
+    }
+
+    // 既定で無効なリントの規則をすべてwarnにする
+    pub fn enable_lints(&mut self) {
+        for rule in Rule::ALL {
+            if rule.default_level() == Level::Allow {
+                self.set(rule, Level::Warn);
+            }
+        }
+    }
+
+    pub fn level(&self, rule: Rule) -> Level {
+        self.levels
+            .get(&rule)
+            .copied()
+            .unwrap_or(rule.default_level())
+    }
+}
+
+/// 水準を適用した警告またはエラー
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub rule: Rule,
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.level {
+            Level::Deny => tr!(error),
+            _ => tr!(warning),
+        };
+        write!(
+            f,
+            "{}: {}: {} [{}]",
+            self.location, severity, self.message, self.rule
+        )
+    }
+}
+
+/// 警告に水準とプラグマを適用し、allowでないものを返す。linesはプラグマを探す前処理済みの行
+pub fn diagnostics(
+    warnings: Vec<Warning>,
+    levels: &LintLevels,
+    lines: &[SourceLine],
+) -> Result<Vec<Diagnostic>> {
+    let pragmas = pragmas(lines)?;
+    let diagnostics = warnings
+        .into_iter()
+        .filter_map(|warning| {
+            //マクロ展開の中の警告には、呼び出した行の前のプラグマも適用する
+            let level = pragmas
+                .iter()
+                .filter(|(location, _, rule)| {
+                    *rule == warning.rule
+                        && call_sites(&warning.location).any(|site| location.is_same_line(site))
+                })
+                .map(|(_, level, _)| *level)
+                .next_back()
+                .unwrap_or(levels.level(warning.rule));
+            let level = match level {
+                Level::Warn if levels.deny_warnings => Level::Deny,
+                level => level,
+            };
+            (level != Level::Allow).then_some(Diagnostic {
+                level,
+                rule: warning.rule,
+                location: warning.location,
+                message: warning.message,
+            })
+        })
+        .collect();
+    Ok(diagnostics)
+}
+
+// 位置とその展開元を、内側のマクロから順にたどる
+fn call_sites(location: &Location) -> impl Iterator<Item = &Location> {
+    iter::successors(Some(location), |location| {
+        location
+            .expansion
+            .as_ref()
+            .map(|expansion| &expansion.call_site)
+    })
+}
+
+// プラグマの(適用する行の位置, 水準, 規則)を集める。プラグマはその次の命令の行に適用する。
+// 次の命令がマクロ展開で生成された行であれば、プラグマと同じ階層にあるマクロの呼び出しに適用する
+fn pragmas(lines: &[SourceLine]) -> Result<Vec<(Location, Level, Rule)>> {
+    let mut pragmas = Vec::new();
+    let mut pending: Vec<(&Location, Level, Rule)> = Vec::new();
+    for line in lines.iter() {
+        let text = line.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match text.strip_prefix(COMMENT_TOKEN) {
+            Some(comment) => {
+                if let Some(pragma) = comment.trim().strip_prefix(PRAGMA_PREFIX) {
+                    let parsed = parse_pragma(pragma).with_context(|| line.location.clone())?;
+                    pending.extend(
+                        parsed
+                            .into_iter()
+                            .map(|(level, rule)| (&line.location, level, rule)),
+                    );
+                }
+            }
+            None => pragmas.extend(pending.drain(..).map(|(pragma, level, rule)| {
+                let target = call_sites(&line.location)
+                    .find(|site| site.expansion == pragma.expansion)
+                    .unwrap_or(&line.location);
+                (target.clone(), level, rule)
+            })),
+        }
+    }
+    Ok(pragmas)
+}
+
+// `allow(unused-label, unused-variable)`を読み取る
+fn parse_pragma(pragma: &str) -> Result<Vec<(Level, Rule)>> {
+    let (level, rules) = pragma
+        .strip_suffix(')')
+        .and_then(|pragma| pragma.split_once('('))
+        .ok_or_else(|| anyhow!(tr!(malformed_pragma, pragma)))?;
+    let level: Level = level.trim().parse()?;
+    rules
+        .split(RULE_SEPARATOR)
+        .map(|rule| Ok((level, rule.trim().parse()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::source_lines;
+
+    fn warning(line: usize, rule: Rule) -> Warning {
+        Warning {
+            location: Location::new(line),
+            message: format!("{} at line {}", rule, line),
+            rule,
+        }
+    }
+
+    #[test]
+    fn test_levels() -> Result<()> {
+        let mut levels = LintLevels::default();
+        assert_eq!(levels.level(Rule::UnusedLabel), Level::Warn);
+        assert_eq!(levels.level(Rule::UnreachableCode), Level::Allow);
+        levels.enable_lints();
+        assert_eq!(levels.level(Rule::UnreachableCode), Level::Warn);
+        levels.set("unused-label".parse()?, "deny".parse()?);
+        assert_eq!(levels.level(Rule::UnusedLabel), Level::Deny);
+
+        assert!("unused".parse::<Rule>().is_err());
+        assert!("error".parse::<Level>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics() -> Result<()> {
+        let lines = source_lines(
+            "// hackasm:allow(unused-label)\n(START)\n\n// hackasm:deny(unused-variable, write-only-variable)\n\
+             // 説明のコメント\n.var x\n(END)",
+        );
+        let warnings = vec![
+            warning(2, Rule::UnusedLabel),
+            warning(6, Rule::UnusedVariable),
+            warning(7, Rule::UnusedLabel),
+            warning(7, Rule::UnreachableCode),
+        ];
+        let result = diagnostics(warnings, &LintLevels::default(), &lines)?;
+        assert_eq!(
+            result.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            vec![
+                "line 6: error: unused-variable at line 6 [unused-variable]",
+                "line 7: warning: unused-label at line 7 [unused-label]",
+            ]
+        );
+
+        let levels = LintLevels {
+            deny_warnings: true,
+            ..Default::default()
+        };
+        let result = diagnostics(vec![warning(7, Rule::UnusedLabel)], &levels, &lines)?;
+        assert_eq!(result[0].level, Level::Deny);
+
+        let error = diagnostics_error("// hackasm:allow(unused)\n@0");
+        assert_eq!(error, "line 1: unknown lint rule \"unused\"");
+        let error = diagnostics_error("// hackasm:allow unused-label\n@0");
+        assert_eq!(
+            error,
+            "line 1: expected hackasm:LEVEL(RULE, ...) but found \"allow unused-label\""
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics_in_expansion() -> Result<()> {
+        let source = ".macro SKIP\n(first)\n@0\n// hackasm:warn(unused-label)\n(kept)\n.endm\n\
+                      // hackasm:allow(unused-label)\nSKIP\nSKIP";
+        let lines = parser::macros::expand(source_lines(source))?;
+        //展開されたラベルの行ごとに警告を作る
+        let warnings: Vec<Warning> = lines
+            .iter()
+            .filter(|line| line.text.starts_with('('))
+            .map(|line| Warning {
+                location: line.location.clone(),
+                message: line.text.clone(),
+                rule: Rule::UnusedLabel,
+            })
+            .collect();
+        let result = diagnostics(warnings, &LintLevels::default(), &lines)?;
+        //呼び出しの前のプラグマは展開全体に、マクロ本体の中のプラグマはその次の行に適用する
+        assert_eq!(
+            result.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            vec![
+                "line 5 (in expansion of macro SKIP at line 8): warning: (SKIP:kept:1) [unused-label]",
+                "line 2 (in expansion of macro SKIP at line 9): warning: (SKIP:first:2) [unused-label]",
+                "line 5 (in expansion of macro SKIP at line 9): warning: (SKIP:kept:2) [unused-label]",
+            ]
+        );
+        Ok(())
+    }
+
+    fn diagnostics_error(source: &str) -> String {
+        let error =
+            diagnostics(Vec::new(), &LintLevels::default(), &source_lines(source)).unwrap_err();
+        format!("{:#}", error)
+    }
+}
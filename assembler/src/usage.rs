@@ -0,0 +1,149 @@
+//! 使われていないラベルと変数の警告
+//!
+//! シンボル表に記録した参照の位置と、second_passで数えた`M=`による書き込みの回数から、
+//! 参照されないラベル、1回しか使われない変数、書き込むだけで読まれない変数を見つける。
+//! 名前が`_`で始まるシンボルは意図して残しているものとみなし、警告しない。
+
+use crate::{rules::Rule, Warning};
+use message::tr;
+use parser::{InstructionType, Parser};
+use std::collections::{HashMap, HashSet};
+use symbol_table::{SymbolKind, SymbolTable};
+
+// 警告しないシンボル名の接頭辞
+const SILENCED_PREFIX: char = '_';
+
+#[derive(Debug, Default)]
+pub struct Usage {
+    // 直前のA命令で指定した変数
+    pending: Option<String>,
+    // 変数ごとの、`M=`で書き込むだけで値を読まなかった参照の数
+    writes: HashMap<String, usize>,
+}
+
+impl Usage {
+    // 現在の命令を見て、直前のA命令の変数への書き込みを数える
+    pub fn record(&mut self, asm_parser: &Parser, symbol_table: &SymbolTable) {
+        let pending = self.pending.take();
+        let Ok(Some(instruction_type)) = asm_parser.instruction_type() else {
+            return;
+        };
+        match instruction_type {
+            InstructionType::A => {
+                self.pending = asm_parser
+                    .symbol()
+                    .ok()
+                    .flatten()
+                    .filter(|symbol| symbol_table.kind(symbol) == Some(SymbolKind::Variable));
+            }
+            InstructionType::C => {
+                let (Some(symbol), Ok(dest), Ok(comp)) =
+                    (pending, asm_parser.dest(), asm_parser.comp())
+                else {
+                    return;
+                };
+                if dest.contains('M') && !comp.contains('M') {
+                    *self.writes.entry(symbol).or_default() += 1;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // implicitは宣言せずに使った変数。割り当て時に警告済みなので、使われた回数は警告しない
+    pub fn warnings(&self, symbol_table: &SymbolTable, implicit: &HashSet<&str>) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        for info in symbol_table.iter() {
+            let Some(location) = &info.definition else {
+                continue;
+            };
+            if info.name.starts_with(SILENCED_PREFIX) {
+                continue;
+            }
+            let references = info.references.len();
+            let writes = self.writes.get(&info.name).copied().unwrap_or(0);
+            let (rule, message) = match info.kind {
+                SymbolKind::Label if references == 0 => {
+                    (Rule::UnusedLabel, tr!(unused_label, info.name))
+                }
+                SymbolKind::Variable if references > 0 && writes == references => {
+                    (Rule::WriteOnlyVariable, tr!(write_only_variable, info.name))
+                }
+                SymbolKind::Variable if implicit.contains(info.name.as_str()) => continue,
+                SymbolKind::Variable if references == 0 => {
+                    (Rule::UnusedVariable, tr!(unused_variable, info.name))
+                }
+                SymbolKind::Variable if references == 1 => {
+                    (Rule::SingleUseVariable, tr!(single_use_variable, info.name))
+                }
+                _ => continue,
+            };
+            warnings.push(Warning {
+                location: location.clone(),
+                message,
+                rule,
+            });
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{first_pass, second_pass, Options};
+    use anyhow::Result;
+
+    fn usage_warnings(source: &str) -> Result<Vec<String>> {
+        let mut symbol_table = SymbolTable::new();
+        let mut warnings = Vec::new();
+        let options = Options::default();
+        let definitions = first_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &options,
+        )?;
+        second_pass(
+            &mut Parser::from_source(source),
+            &mut symbol_table,
+            &definitions,
+            &options,
+            &mut warnings,
+        )?;
+        Ok(warnings.iter().map(|w| w.to_string()).collect())
+    }
+
+    #[test]
+    fn test_warnings() -> Result<()> {
+        let source = ".var sum\n.var count\n.var flag\n.var unused\n.var _debug\n\
+                      (LOOP)\n@sum\nM=0\n@sum\nD=M\n@count\nM=D\n@flag\nM=1\n@flag\nM=0\n\
+                      (END)\n@LOOP\n0;JMP\n(_entry)";
+        assert_eq!(
+            usage_warnings(source)?,
+            vec![
+                "line 2: warning: variable count is written but never read",
+                "line 3: warning: variable flag is written but never read",
+                "line 4: warning: variable unused is never used",
+                "line 17: warning: label END is never referenced",
+            ]
+        );
+
+        //書き込みと読み込みがあれば警告しない。式の中での参照は読み込みとみなす
+        let source = ".var i\n.var buffer 4\n@i\nM=0\n@i\nMD=M+1\n@buffer+1\nD=A\n@buffer\nD=M";
+        assert!(usage_warnings(source)?.is_empty());
+
+        let source = ".var total\n@total\nD=M";
+        assert_eq!(
+            usage_warnings(source)?,
+            vec!["line 1: warning: variable total is used only once"]
+        );
+
+        //宣言していない変数は割り当て時の警告だけにする
+        let source = "@i\nD=M";
+        assert_eq!(
+            usage_warnings(source)?,
+            vec!["line 1: warning: symbol i is not declared; allocated as a variable at RAM[16]"]
+        );
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use error_code::ErrorCode;
+use message::tr;
 
 const D_REGISTER_TOKEN: char = 'D';
 const A_REGISTER_TOKEN: char = 'A';
@@ -7,11 +9,35 @@ const PLUS_TOKEN: char = '+';
 const MINUS_TOKEN: char = '-';
 const NOT_TOKEN: char = '!';
 const OR_TOKEN: char = '|';
+const NULL_MNEMONIC: &str = "null";
+// 仕様書のcompニーモニック
+const COMP_MNEMONICS: [&str; 28] = [
+    "0", "1", "-1", "D", "A", "!D", "!A", "-D", "-A", "D+1", "A+1", "D-1", "A-1", "D+A", "D-A",
+    "A-D", "D&A", "D|A", "M", "!M", "-M", "M+1", "M-1", "D+M", "D-M", "M-D", "D&M", "D|M",
+];
+// 交換可能な演算のオペランドを入れ替えた表記と仕様書の表記の対応
+const COMMUTATIVE_COMP_MNEMONICS: [(&str, &str); 6] = [
+    ("A+D", "D+A"),
+    ("A&D", "D&A"),
+    ("A|D", "D|A"),
+    ("M+D", "D+M"),
+    ("M&D", "D&M"),
+    ("M|D", "D|M"),
+];
 
 pub struct Code {}
 
 impl Code {
     pub fn dest(v: &str) -> Result<String> {
+        // null以外はA,D,Mをそれぞれ高々1回ずつ含む組み合わせのみ受け付ける
+        let registers = [A_REGISTER_TOKEN, D_REGISTER_TOKEN, M_REGISTER_TOKEN];
+        let is_valid = v.chars().all(|c| registers.contains(&c))
+            && registers
+                .iter()
+                .all(|register| v.matches(*register).count() <= 1);
+        if v != NULL_MNEMONIC && !is_valid {
+            bail!(ErrorCode::UnknownDest.error(tr!(unknown_dest, v)));
+        }
         Ok(format!(
             "{}{}{}",
             v.contains('A') as u8,
@@ -21,6 +47,13 @@ impl Code {
         .to_string())
     }
     pub fn comp(v: &str) -> Result<String> {
+        let v = COMMUTATIVE_COMP_MNEMONICS
+            .iter()
+            .find(|(alias, _)| *alias == v)
+            .map_or(v, |(_, mnemonic)| *mnemonic);
+        if !COMP_MNEMONICS.contains(&v) {
+            bail!(ErrorCode::UnknownComp.error(tr!(unknown_comp, v)));
+        }
         //0,1,-1は入力を利用していないので固定で返す。
         match v {
             "0" => return Ok("0101010".to_string()),
@@ -93,7 +126,8 @@ impl Code {
             "JNE" => Ok("101".to_string()),
             "JLE" => Ok("110".to_string()),
             "JMP" => Ok("111".to_string()),
-            _ => Ok("000".to_string()),
+            "" | NULL_MNEMONIC => Ok("000".to_string()),
+            _ => bail!(ErrorCode::UnknownJump.error(tr!(unknown_jump, v))),
         }
     }
 }
@@ -123,6 +157,10 @@ mod tests {
         assert_eq!(Code::dest("AM")?, "101");
         assert_eq!(Code::dest("AD")?, "110");
         assert_eq!(Code::dest("ADM")?, "111");
+        assert_eq!(Code::dest("MD")?, "011");
+        assert_eq!(Code::dest("")?, "000");
+        assert!(Code::dest("DD").is_err());
+        assert!(Code::dest("X").is_err());
         Ok(())
     }
 
@@ -159,6 +197,17 @@ mod tests {
         assert_eq!(Code::comp("M-D")?, "1000111");
         assert_eq!(Code::comp("D&M")?, "1000000");
         assert_eq!(Code::comp("D|M")?, "1010101");
+
+        //交換可能な演算はオペランドを入れ替えても同じ
+        assert_eq!(Code::comp("A+D")?, Code::comp("D+A")?);
+        assert_eq!(Code::comp("A&D")?, Code::comp("D&A")?);
+        assert_eq!(Code::comp("A|D")?, Code::comp("D|A")?);
+        assert_eq!(Code::comp("M+D")?, Code::comp("D+M")?);
+        assert_eq!(Code::comp("M&D")?, Code::comp("D&M")?);
+        assert_eq!(Code::comp("M|D")?, Code::comp("D|M")?);
+
+        assert!(Code::comp("D*A").is_err());
+        assert!(Code::comp("").is_err());
         Ok(())
     }
 
@@ -172,6 +221,8 @@ mod tests {
         assert_eq!(Code::jump("JNE")?, "101");
         assert_eq!(Code::jump("JLE")?, "110");
         assert_eq!(Code::jump("JMP")?, "111");
+        assert_eq!(Code::jump("")?, "000");
+        assert!(Code::jump("JUMP").is_err());
 
         Ok(())
     }
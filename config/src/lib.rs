@@ -0,0 +1,167 @@
+//! 設定ファイル(TOML)とコマンドラインによるアセンブラの設定
+//!
+//! ```toml
+//! # trueにするとR0やSCREENなどの既定の定義済みシンボルを使わない
+//! replace_predefined = false
+//! # 変数を割り当て始めるRAMアドレス
+//! variable_base = 16
+//!
+//! [predefined]
+//! LED = 0x6001
+//! UART = 0x6002
+//!
+//! # 警告の規則ごとの水準(allow/warn/deny)
+//! [lints]
+//! unused-label = "allow"
+//! ```
+
+use anyhow::{anyhow, bail, Context, Result};
+use error_code::ErrorCode;
+use message::tr;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+const DEFINE_SEPARATOR: char = '=';
+// 定義済みシンボルに使えるアドレスの最大値(A命令で扱える15ビット)
+const MAX_ADDRESS: u16 = 0x7FFF;
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// 既定の定義済みシンボルを使わず、predefinedだけを定義済みシンボルにする
+    #[serde(default)]
+    pub replace_predefined: bool,
+    /// 追加または上書きする定義済みシンボル
+    #[serde(default)]
+    pub predefined: BTreeMap<String, u16>,
+    /// 変数を割り当て始めるRAMアドレス
+    pub variable_base: Option<u16>,
+    /// 警告の規則の名前と水準
+    #[serde(default)]
+    pub lints: BTreeMap<String, String>,
+}
+
+pub fn load(path: &Path) -> Result<Config> {
+    let text = fs::read_to_string(path).with_context(|| tr!(cannot_read, path.display()))?;
+    parse(&text).with_context(|| tr!(invalid_config_file, path.display()))
+}
+
+pub fn parse(text: &str) -> Result<Config> {
+    let config: Config = toml::from_str(text)?;
+    for (name, address) in config.predefined.iter() {
+        check_name(name)?;
+        check_address(name, *address)?;
+    }
+    Ok(config)
+}
+
+/// `-D NAME=addr`の値を読み取る。アドレスには`0x6001`のようなリテラルも使える
+pub fn parse_define(v: &str) -> Result<(String, u16)> {
+    let (name, address) = v
+        .split_once(DEFINE_SEPARATOR)
+        .filter(|(name, _)| !name.trim().is_empty())
+        .ok_or_else(|| anyhow!(ErrorCode::MalformedDefine.error(tr!(expected_define, v))))?;
+    let name = name.trim();
+    check_name(name)?;
+    let address = evaluate(address)?;
+    let address = u16::try_from(address).map_err(|_| {
+        anyhow!(ErrorCode::ValueOutOfRange.error(tr!(define_out_of_range, address, name)))
+    })?;
+    check_address(name, address)?;
+    Ok((name.to_string(), address))
+}
+
+/// `--variable-base`の値を読み取る。`-D`と同じく`0x20`のようなリテラルも使える
+pub fn parse_address(v: &str) -> Result<u16> {
+    let address = evaluate(v)?;
+    u16::try_from(address).map_err(|_| {
+        anyhow!(ErrorCode::ValueOutOfRange.error(tr!(value_out_of_range, address, u16::MAX)))
+    })
+}
+
+// `.equ`の定数と同じ規則の名前だけを受け付ける
+fn check_name(name: &str) -> Result<()> {
+    if !parser::is_symbol_name(name) {
+        bail!(ErrorCode::InvalidSymbolName.error(tr!(invalid_define_name, name)));
+    }
+    Ok(())
+}
+
+// シンボルを含まない式を計算する
+fn evaluate(v: &str) -> Result<i64> {
+    parser::expression::parse(v)?.evaluate(&|_| None)
+}
+
+fn check_address(name: &str, address: u16) -> Result<()> {
+    if address > MAX_ADDRESS {
+        bail!(ErrorCode::ValueOutOfRange.error(tr!(
+            define_out_of_range_max,
+            address,
+            name,
+            MAX_ADDRESS
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let config = parse(
+            "variable_base = 32\n[predefined]\nLED = 0x6001\nUART = 24578\n\
+             [lints]\nunused-label = \"allow\"\n",
+        )?;
+        assert_eq!(
+            config,
+            Config {
+                replace_predefined: false,
+                predefined: BTreeMap::from([
+                    ("LED".to_string(), 0x6001),
+                    ("UART".to_string(), 24578)
+                ]),
+                variable_base: Some(32),
+                lints: BTreeMap::from([("unused-label".to_string(), "allow".to_string())]),
+            }
+        );
+        assert_eq!(parse("")?, Config::default());
+
+        assert!(parse("unknown = 1").is_err());
+        assert!(parse("[predefined]\nLED = 32768").is_err());
+        assert!(parse("[predefined]\nD = 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_define() -> Result<()> {
+        assert_eq!(parse_define("LED=0x6001")?, ("LED".to_string(), 0x6001));
+        assert_eq!(parse_define("TIMER=24579")?, ("TIMER".to_string(), 24579));
+        let code = |v| error_code::code_of(&parse_define(v).unwrap_err());
+        assert_eq!(code("LED"), Some(ErrorCode::MalformedDefine));
+        assert!(parse_define("=1").is_err());
+        assert!(parse_define("LED=X").is_err());
+        assert_eq!(code("LED=40000"), Some(ErrorCode::ValueOutOfRange));
+        assert_eq!(code("LED=70000"), Some(ErrorCode::ValueOutOfRange));
+        assert_eq!(
+            parse_define("1X=5").unwrap_err().to_string(),
+            "invalid symbol name: \"1X\""
+        );
+        assert_eq!(code("A=5"), Some(ErrorCode::InvalidSymbolName));
+        assert!(parse_define("LED BLUE=5").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_address() -> Result<()> {
+        assert_eq!(parse_address("0x20")?, 32);
+        assert_eq!(parse_address("100")?, 100);
+        assert_eq!(
+            error_code::code_of(&parse_address("-1").unwrap_err()),
+            Some(ErrorCode::ValueOutOfRange)
+        );
+        assert!(parse_address("X").is_err());
+        Ok(())
+    }
+}
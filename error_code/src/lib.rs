@@ -0,0 +1,536 @@
+//! エラーの安定した番号(`H0003`など)と`--explain`で表示する説明
+//!
+//! 番号は一度割り当てたら変えない。不要になった番号も再利用しない。
+
+use anyhow::{anyhow, Result};
+use message::tr;
+use std::{fmt, str::FromStr};
+
+const CODE_PREFIX: char = 'H';
+
+/// エラーの種類。値がそのまま番号になる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ErrorCode {
+    InvalidInstruction = 1,
+    UnknownDest = 2,
+    UnknownComp = 3,
+    UnknownJump = 4,
+    DuplicateSymbol = 5,
+    UnknownDirective = 6,
+    MalformedDirective = 7,
+    InvalidString = 8,
+    InvalidExpression = 9,
+    InvalidNumber = 10,
+    UndefinedSymbol = 11,
+    CurrentAddressNotAllowed = 12,
+    LocalLabelNotFound = 13,
+    ArithmeticError = 14,
+    IncludeNotFound = 15,
+    IncludeCycle = 16,
+    MalformedInclude = 17,
+    DuplicateMacro = 18,
+    MalformedMacro = 19,
+    MacroArguments = 20,
+    MacroTooDeep = 21,
+    RomOverflow = 22,
+    InvalidSymbolName = 23,
+    InvalidSize = 24,
+    ValueOutOfRange = 25,
+    LabelOutOfScope = 26,
+    InvalidRamAddress = 27,
+    OutOfRam = 28,
+    VariableOverlap = 29,
+    UndeclaredSymbol = 30,
+    MalformedDefine = 31,
+}
+
+impl ErrorCode {
+    /// すべての種類
+    pub const ALL: [ErrorCode; 31] = [
+        ErrorCode::InvalidInstruction,
+        ErrorCode::UnknownDest,
+        ErrorCode::UnknownComp,
+        ErrorCode::UnknownJump,
+        ErrorCode::DuplicateSymbol,
+        ErrorCode::UnknownDirective,
+        ErrorCode::MalformedDirective,
+        ErrorCode::InvalidString,
+        ErrorCode::InvalidExpression,
+        ErrorCode::InvalidNumber,
+        ErrorCode::UndefinedSymbol,
+        ErrorCode::CurrentAddressNotAllowed,
+        ErrorCode::LocalLabelNotFound,
+        ErrorCode::ArithmeticError,
+        ErrorCode::IncludeNotFound,
+        ErrorCode::IncludeCycle,
+        ErrorCode::MalformedInclude,
+        ErrorCode::DuplicateMacro,
+        ErrorCode::MalformedMacro,
+        ErrorCode::MacroArguments,
+        ErrorCode::MacroTooDeep,
+        ErrorCode::RomOverflow,
+        ErrorCode::InvalidSymbolName,
+        ErrorCode::InvalidSize,
+        ErrorCode::ValueOutOfRange,
+        ErrorCode::LabelOutOfScope,
+        ErrorCode::InvalidRamAddress,
+        ErrorCode::OutOfRam,
+        ErrorCode::VariableOverlap,
+        ErrorCode::UndeclaredSymbol,
+        ErrorCode::MalformedDefine,
+    ];
+
+    /// `H0003`のような番号
+    pub fn code(&self) -> String {
+        format!("{}{:04}", CODE_PREFIX, *self as u16)
+    }
+
+    /// 種類を表す短い説明
+    pub fn title(&self) -> String {
+        match self {
+            ErrorCode::InvalidInstruction => tr!(title_invalid_instruction),
+            ErrorCode::UnknownDest => tr!(title_unknown_dest),
+            ErrorCode::UnknownComp => tr!(title_unknown_comp),
+            ErrorCode::UnknownJump => tr!(title_unknown_jump),
+            ErrorCode::DuplicateSymbol => tr!(title_duplicate_symbol),
+            ErrorCode::UnknownDirective => tr!(title_unknown_directive),
+            ErrorCode::MalformedDirective => tr!(title_malformed_directive),
+            ErrorCode::InvalidString => tr!(title_invalid_string),
+            ErrorCode::InvalidExpression => tr!(title_invalid_expression),
+            ErrorCode::InvalidNumber => tr!(title_invalid_number),
+            ErrorCode::UndefinedSymbol => tr!(title_undefined_symbol),
+            ErrorCode::CurrentAddressNotAllowed => tr!(title_current_address_not_allowed),
+            ErrorCode::LocalLabelNotFound => tr!(title_local_label_not_found),
+            ErrorCode::ArithmeticError => tr!(title_arithmetic_error),
+            ErrorCode::IncludeNotFound => tr!(title_include_not_found),
+            ErrorCode::IncludeCycle => tr!(title_include_cycle),
+            ErrorCode::MalformedInclude => tr!(title_malformed_include),
+            ErrorCode::DuplicateMacro => tr!(title_duplicate_macro),
+            ErrorCode::MalformedMacro => tr!(title_malformed_macro),
+            ErrorCode::MacroArguments => tr!(title_macro_arguments),
+            ErrorCode::MacroTooDeep => tr!(title_macro_too_deep),
+            ErrorCode::RomOverflow => tr!(title_rom_overflow),
+            ErrorCode::InvalidSymbolName => tr!(title_invalid_symbol_name),
+            ErrorCode::InvalidSize => tr!(title_invalid_size),
+            ErrorCode::ValueOutOfRange => tr!(title_value_out_of_range),
+            ErrorCode::LabelOutOfScope => tr!(title_label_out_of_scope),
+            ErrorCode::InvalidRamAddress => tr!(title_invalid_ram_address),
+            ErrorCode::OutOfRam => tr!(title_out_of_ram),
+            ErrorCode::VariableOverlap => tr!(title_variable_overlap),
+            ErrorCode::UndeclaredSymbol => tr!(title_undeclared_symbol),
+            ErrorCode::MalformedDefine => tr!(title_malformed_define),
+        }
+    }
+
+    /// `--explain`で表示する、正しい例と誤った例を含む説明
+    pub fn explanation(&self) -> String {
+        let explanation = self.explanation_text();
+        format!(
+            "{}\n\n{}:\n\n{}\n\n{}:\n\n{}",
+            explanation.text,
+            tr!(incorrect),
+            explanation.incorrect,
+            tr!(correct),
+            explanation.correct
+        )
+    }
+
+    fn explanation_text(&self) -> Explanation {
+        match self {
+            ErrorCode::InvalidInstruction => Explanation {
+                text: tr!(explain_invalid_instruction),
+                incorrect: "    LOAD 5
+    D+1",
+                correct: "    @5
+    D=D+1",
+            },
+            ErrorCode::UnknownDest => Explanation {
+                text: tr!(explain_unknown_dest),
+                incorrect: "    X=D
+    MM=D",
+                correct: "    M=D
+    AMD=D+1",
+            },
+            ErrorCode::UnknownComp => Explanation {
+                text: tr!(explain_unknown_comp),
+                incorrect: "    D=D*2
+    D=D+2
+    D=A+M",
+                correct: "    D=D+A
+    D=M+1
+    D=!D",
+            },
+            ErrorCode::UnknownJump => Explanation {
+                text: tr!(explain_unknown_jump),
+                incorrect: "    D;JUMP
+    0;jmp",
+                correct: "    D;JGT
+    0;JMP",
+            },
+            ErrorCode::DuplicateSymbol => Explanation {
+                text: tr!(explain_duplicate_symbol),
+                incorrect: "    .equ WIDTH 32
+    .equ WIDTH 64
+    .var buffer 4
+(buffer)",
+                correct: "    .equ WIDTH 32
+    .equ HEIGHT 64
+    .var buffer 4
+(BUFFER_READY)",
+            },
+            ErrorCode::UnknownDirective => Explanation {
+                text: tr!(explain_unknown_directive),
+                incorrect: "    .word 5",
+                correct: "    .data table 5",
+            },
+            ErrorCode::MalformedDirective => Explanation {
+                text: tr!(explain_malformed_directive),
+                incorrect: "    .equ WIDTH
+    .data table",
+                correct: "    .equ WIDTH 32
+    .data table 1, 2, 3",
+            },
+            ErrorCode::InvalidString => Explanation {
+                text: tr!(explain_invalid_string),
+                incorrect: "    .string message HELLO
+    .string message \"say \"hi\"\"",
+                correct: "    .string message \"HELLO\"
+    .string message \"say \\\"hi\\\"\"",
+            },
+            ErrorCode::InvalidExpression => Explanation {
+                text: tr!(explain_invalid_expression),
+                incorrect: "    @WIDTH*
+    @(WIDTH+1",
+                correct: "    @WIDTH*2
+    @(WIDTH+1)*2",
+            },
+            ErrorCode::InvalidNumber => Explanation {
+                text: tr!(explain_invalid_number),
+                incorrect: "    @0x
+    @0b102",
+                correct: "    @0x4000
+    @0b101",
+            },
+            ErrorCode::UndefinedSymbol => Explanation {
+                text: tr!(explain_undefined_symbol),
+                incorrect: "    .equ SIZE WIDTH*2
+    .equ WIDTH 32",
+                correct: "    .equ WIDTH 32
+    .equ SIZE WIDTH*2",
+            },
+            ErrorCode::CurrentAddressNotAllowed => Explanation {
+                text: tr!(explain_current_address_not_allowed),
+                incorrect: "    HackAssembler -f Main.asm -D LED=$",
+                correct: "    HackAssembler -f Main.asm -D LED=0x6001",
+            },
+            ErrorCode::LocalLabelNotFound => Explanation {
+                text: tr!(explain_local_label_not_found),
+                incorrect: "    1:
+    @1f
+    0;JMP",
+                correct: "    1:
+    @1b
+    0;JMP",
+            },
+            ErrorCode::ArithmeticError => Explanation {
+                text: tr!(explain_arithmetic_error),
+                incorrect: "    @WIDTH/0",
+                correct: "    @WIDTH/2",
+            },
+            ErrorCode::IncludeNotFound => Explanation {
+                text: tr!(explain_include_not_found),
+                incorrect: "    .include \"missing.asm\"",
+                correct: "    .include \"lib/math.asm\"",
+            },
+            ErrorCode::IncludeCycle => Explanation {
+                text: tr!(explain_include_cycle),
+                incorrect: "    // a.asm
+    .include \"a.asm\"",
+                correct: "    // a.asm
+    .include \"b.asm\"",
+            },
+            ErrorCode::MalformedInclude => Explanation {
+                text: tr!(explain_malformed_include),
+                incorrect: "    .include math.asm",
+                correct: "    .include \"math.asm\"",
+            },
+            ErrorCode::DuplicateMacro => Explanation {
+                text: tr!(explain_duplicate_macro),
+                incorrect: "    .macro INC x
+    .endm
+    .macro INC x
+    .endm",
+                correct: "    .macro INC x
+    .endm
+    .macro DEC x
+    .endm",
+            },
+            ErrorCode::MalformedMacro => Explanation {
+                text: tr!(explain_malformed_macro),
+                incorrect: "    .macro INC x, x
+        @%x
+        M=M+1",
+                correct: "    .macro INC x
+        @%x
+        M=M+1
+    .endm",
+            },
+            ErrorCode::MacroArguments => Explanation {
+                text: tr!(explain_macro_arguments),
+                incorrect: "    .macro INC x
+        @%y
+        M=M+1
+    .endm
+    INC i, j",
+                correct: "    .macro INC x
+        @%x
+        M=M+1
+    .endm
+    INC i",
+            },
+            ErrorCode::MacroTooDeep => Explanation {
+                text: tr!(explain_macro_too_deep),
+                incorrect: "    .macro LOOP
+        LOOP
+    .endm",
+                correct: "    .macro LOOP
+        0;JMP
+    .endm",
+            },
+            ErrorCode::RomOverflow => Explanation {
+                text: tr!(explain_rom_overflow),
+                incorrect: "    // 32769 instructions
+    @0
+    D=A
+    ...",
+                correct: "    // at most 32768 instructions
+    @0
+    D=A
+    ...",
+            },
+            ErrorCode::InvalidSymbolName => Explanation {
+                text: tr!(explain_invalid_symbol_name),
+                incorrect: "    .equ 1ST 1
+    .var D",
+                correct: "    .equ FIRST 1
+    .var count",
+            },
+            ErrorCode::InvalidSize => Explanation {
+                text: tr!(explain_invalid_size),
+                incorrect: "    .var buffer 0
+    .record Point x 0, y",
+                correct: "    .var buffer 4
+    .record Point x, y",
+            },
+            ErrorCode::ValueOutOfRange => Explanation {
+                text: tr!(explain_value_out_of_range),
+                incorrect: "    @40000
+    .data table 70000",
+                correct: "    @0x4000
+    .data table 65535",
+            },
+            ErrorCode::LabelOutOfScope => Explanation {
+                text: tr!(explain_label_out_of_scope),
+                incorrect: "(.loop)
+    @.loop
+    0;JMP",
+                correct: "(MAIN)
+(.loop)
+    @.loop
+    0;JMP",
+            },
+            ErrorCode::InvalidRamAddress => Explanation {
+                text: tr!(explain_invalid_ram_address),
+                incorrect: "    .org 5
+    .org 0x4000",
+                correct: "    .org 32
+    .org 0x1000",
+            },
+            ErrorCode::OutOfRam => Explanation {
+                text: tr!(explain_out_of_ram),
+                incorrect: "    .var buffer 0x4000",
+                correct: "    .var buffer 0x100",
+            },
+            ErrorCode::VariableOverlap => Explanation {
+                text: tr!(explain_variable_overlap),
+                incorrect: "    .var a 4
+    .org 17
+    .var b",
+                correct: "    .var a 4
+    .org 20
+    .var b",
+            },
+            ErrorCode::UndeclaredSymbol => Explanation {
+                text: tr!(explain_undeclared_symbol),
+                incorrect: "    .var counter
+    @coutner
+    M=M+1",
+                correct: "    .var counter
+    @counter
+    M=M+1",
+            },
+            ErrorCode::MalformedDefine => Explanation {
+                text: tr!(explain_malformed_define),
+                incorrect: "    HackAssembler -f Main.asm -D LED",
+                correct: "    HackAssembler -f Main.asm -D LED=0x6001",
+            },
+        }
+    }
+
+    /// この種類のエラーを作る
+    pub fn error(self, message: String) -> CodedError {
+        CodedError {
+            code: self,
+            message,
+        }
+    }
+}
+
+// 現在の言語の説明の本文と、誤った例と正しい例。例は言語によらず共通
+struct Explanation {
+    text: String,
+    incorrect: &'static str,
+    correct: &'static str,
+}
+
+impl FromStr for ErrorCode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|code| code.code().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow!(tr!(unknown_error_code, s)))
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.code())
+    }
+}
+
+/// 番号付きのエラー。表示はメッセージだけで、番号はcode_ofで取り出す
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// エラーの原因をたどり、番号の付いたエラーがあればその番号を返す
+pub fn code_of(error: &anyhow::Error) -> Option<ErrorCode> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<CodedError>())
+        .map(|error| error.code)
+}
+
+/// `--explain`の出力
+pub fn explain(code: &str) -> Result<String> {
+    let code: ErrorCode = code.parse()?;
+    Ok(format!(
+        "{}: {}\n\n{}\n",
+        code,
+        code.title(),
+        code.explanation()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use message::Language;
+
+    #[test]
+    fn test_code() -> Result<()> {
+        assert_eq!(ErrorCode::InvalidInstruction.code(), "H0001");
+        assert_eq!(ErrorCode::UnknownComp.code(), "H0003");
+        assert_eq!("h0003".parse::<ErrorCode>()?, ErrorCode::UnknownComp);
+        assert!("H9999".parse::<ErrorCode>().is_err());
+        assert!("E0003".parse::<ErrorCode>().is_err());
+        for code in ErrorCode::ALL {
+            assert!(code.explanation().contains("Incorrect:"), "{}", code);
+            assert!(code.explanation().contains("Correct:"), "{}", code);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_codes() {
+        //番号は一度割り当てたら変わらない
+        let codes: Vec<(ErrorCode, String)> = ErrorCode::ALL
+            .iter()
+            .map(|code| (*code, code.code()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (ErrorCode::InvalidInstruction, "H0001".to_string()),
+                (ErrorCode::UnknownDest, "H0002".to_string()),
+                (ErrorCode::UnknownComp, "H0003".to_string()),
+                (ErrorCode::UnknownJump, "H0004".to_string()),
+                (ErrorCode::DuplicateSymbol, "H0005".to_string()),
+                (ErrorCode::UnknownDirective, "H0006".to_string()),
+                (ErrorCode::MalformedDirective, "H0007".to_string()),
+                (ErrorCode::InvalidString, "H0008".to_string()),
+                (ErrorCode::InvalidExpression, "H0009".to_string()),
+                (ErrorCode::InvalidNumber, "H0010".to_string()),
+                (ErrorCode::UndefinedSymbol, "H0011".to_string()),
+                (ErrorCode::CurrentAddressNotAllowed, "H0012".to_string()),
+                (ErrorCode::LocalLabelNotFound, "H0013".to_string()),
+                (ErrorCode::ArithmeticError, "H0014".to_string()),
+                (ErrorCode::IncludeNotFound, "H0015".to_string()),
+                (ErrorCode::IncludeCycle, "H0016".to_string()),
+                (ErrorCode::MalformedInclude, "H0017".to_string()),
+                (ErrorCode::DuplicateMacro, "H0018".to_string()),
+                (ErrorCode::MalformedMacro, "H0019".to_string()),
+                (ErrorCode::MacroArguments, "H0020".to_string()),
+                (ErrorCode::MacroTooDeep, "H0021".to_string()),
+                (ErrorCode::RomOverflow, "H0022".to_string()),
+                (ErrorCode::InvalidSymbolName, "H0023".to_string()),
+                (ErrorCode::InvalidSize, "H0024".to_string()),
+                (ErrorCode::ValueOutOfRange, "H0025".to_string()),
+                (ErrorCode::LabelOutOfScope, "H0026".to_string()),
+                (ErrorCode::InvalidRamAddress, "H0027".to_string()),
+                (ErrorCode::OutOfRam, "H0028".to_string()),
+                (ErrorCode::VariableOverlap, "H0029".to_string()),
+                (ErrorCode::UndeclaredSymbol, "H0030".to_string()),
+                (ErrorCode::MalformedDefine, "H0031".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_code_of() {
+        let error: Result<()> = Err(ErrorCode::UnknownJump
+            .error("unknown jump".to_string())
+            .into());
+        let error = error.context("line 3").unwrap_err();
+        assert_eq!(format!("{:#}", error), "line 3: unknown jump");
+        assert_eq!(code_of(&error), Some(ErrorCode::UnknownJump));
+        assert_eq!(code_of(&anyhow!("other error")), None);
+    }
+
+    #[test]
+    fn test_explain() -> Result<()> {
+        let explanation = explain("H0003")?;
+        assert!(explanation.starts_with("H0003: unknown comp mnemonic\n\n"));
+        assert!(explanation.contains("D=D*2"));
+
+        message::set_language(Language::Japanese);
+        let explanation = explain("H0003")?;
+        assert!(explanation.starts_with("H0003: 不明なcompのニーモニック\n\n"));
+        assert!(explanation.contains("誤った例:\n\n    D=D*2"));
+        Ok(())
+    }
+}
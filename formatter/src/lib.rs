@@ -0,0 +1,259 @@
+//! `fmt`サブコマンドで使う、.asmファイルの整形
+//!
+//! ラベルとディレクティブは行頭から書き、命令とマクロの呼び出しは字下げする。
+//! 後ろにコメントのある行が続く場合は、コメントを同じ列に揃える。
+
+use parser::{
+    cst::{LineKind, SyntaxLine, SyntaxTree, TokenKind},
+    InstructionType,
+};
+
+const INDENT: &str = "    ";
+const COMMENT_TOKEN: &str = "//";
+const DEST_TOKEN: char = '=';
+const NEWLINE: &str = "\n";
+// destのレジスタを並べる順。DMではなくMD、DMAではなくAMDと書く
+const DEST_ORDER: [char; 3] = ['A', 'M', 'D'];
+
+// 整形する1行
+#[derive(Debug, PartialEq)]
+enum Line {
+    Blank,
+    // コメントだけの行
+    Comment(String),
+    // 命令、ラベルまたはディレクティブと、その後ろのコメント
+    Code {
+        indented: bool,
+        code: String,
+        comment: Option<String>,
+    },
+}
+
+/// ソースを整形した結果を返す。改行はソースの最初の行に合わせて`\n`か`\r\n`にする
+pub fn format(source: &str) -> String {
+    let tree = SyntaxTree::parse(source);
+    let newline = tree
+        .lines
+        .first()
+        .and_then(|line| line.tokens.last())
+        .filter(|token| token.kind == TokenKind::Newline)
+        .map_or(NEWLINE, |token| token.text.as_str());
+    let mut lines: Vec<Line> = Vec::new();
+    for line in tree.lines.iter().map(parse_line) {
+        // 空行は続けず、先頭にも置かない
+        if line == Line::Blank && matches!(lines.last(), None | Some(Line::Blank)) {
+            continue;
+        }
+        lines.push(line);
+    }
+    if lines.last() == Some(&Line::Blank) {
+        lines.pop();
+    }
+    let columns = comment_columns(&lines);
+    let mut formatted = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        match line {
+            Line::Blank => (),
+            Line::Comment(comment) => {
+                // コメントだけの行は次の行に合わせて字下げする
+                if next_indented(&lines[i + 1..]) {
+                    formatted.push_str(INDENT);
+                }
+                formatted.push_str(comment);
+            }
+            Line::Code {
+                indented,
+                code,
+                comment,
+            } => {
+                let indent = if *indented { INDENT } else { "" };
+                let text = format!("{}{}", indent, code);
+                match comment {
+                    Some(comment) => formatted.push_str(&format!(
+                        "{:<width$} {}",
+                        text,
+                        comment,
+                        width = columns[i]
+                    )),
+                    None => formatted.push_str(&text),
+                }
+            }
+        }
+        formatted.push_str(newline);
+    }
+    formatted
+}
+
+/// 整形前と整形後で最初に異なる行の番号。改行や最後の改行の有無だけが違う場合も、その行を指す
+pub fn first_difference(source: &str, formatted: &str) -> usize {
+    let same = source
+        .split_inclusive(NEWLINE)
+        .zip(formatted.split_inclusive(NEWLINE))
+        .take_while(|(before, after)| before == after)
+        .count();
+    //整形前のソースの行の範囲に収める
+    (same + 1).min(source.split_inclusive(NEWLINE).count().max(1))
+}
+
+fn parse_line(line: &SyntaxLine) -> Line {
+    let comment = line.comment().map(|token| normalize_comment(&token.text));
+    match (line.kind, comment) {
+        (LineKind::Empty, None) => Line::Blank,
+        (LineKind::Empty, Some(comment)) => Line::Comment(comment),
+        (kind, comment) => Line::Code {
+            indented: !matches!(
+                kind,
+                LineKind::Instruction(InstructionType::L | InstructionType::Directive)
+            ),
+            code: normalize_code(line),
+            comment,
+        },
+    }
+}
+
+// 空行の前までで最初の命令などの行が字下げされるか
+fn next_indented(lines: &[Line]) -> bool {
+    lines
+        .iter()
+        .find_map(|line| match line {
+            Line::Blank => Some(false),
+            Line::Comment(_) => None,
+            Line::Code { indented, .. } => Some(*indented),
+        })
+        .unwrap_or(false)
+}
+
+// 後ろにコメントのある行が続く範囲ごとに、コメントの前に置く幅を求める
+fn comment_columns(lines: &[Line]) -> Vec<usize> {
+    let widths: Vec<Option<usize>> = lines
+        .iter()
+        .map(|line| match line {
+            Line::Code {
+                indented,
+                code,
+                comment: Some(_),
+            } => Some(code.chars().count() + if *indented { INDENT.len() } else { 0 }),
+            _ => None,
+        })
+        .collect();
+    widths
+        .chunk_by(|a, b| a.is_some() && b.is_some())
+        .flat_map(|group| {
+            let width = group.iter().flatten().max().copied().unwrap_or(0);
+            vec![width; group.len()]
+        })
+        .collect()
+}
+
+// C命令は空白を除いてdestの順を揃え、ディレクティブとマクロの呼び出しは空白を1つにまとめる
+fn normalize_code(line: &SyntaxLine) -> String {
+    match line.kind {
+        LineKind::Instruction(InstructionType::A | InstructionType::L) => line.code(),
+        LineKind::Instruction(InstructionType::C) => {
+            let code = join_tokens(line, "");
+            match code.split_once(DEST_TOKEN) {
+                Some((dest, rest)) => format!("{}{}{}", normalize_dest(dest), DEST_TOKEN, rest),
+                None => code,
+            }
+        }
+        _ => join_tokens(line, " "),
+    }
+}
+
+// 命令の部分のトークンを、間の空白をseparatorに置き換えてつなげる。文字列の中は1つのトークンなのでそのまま
+fn join_tokens(line: &SyntaxLine, separator: &str) -> String {
+    let mut code = String::new();
+    let mut spaced = false;
+    for token in &line.tokens {
+        match token.kind {
+            TokenKind::Whitespace => spaced = true,
+            kind if kind.is_trivia() => (),
+            _ => {
+                if spaced && !code.is_empty() {
+                    code.push_str(separator);
+                }
+                spaced = false;
+                code.push_str(&token.text);
+            }
+        }
+    }
+    code
+}
+
+// A、M、Dをそれぞれ高々1回含むdestだけを並べ替える
+fn normalize_dest(dest: &str) -> String {
+    let is_valid = dest.chars().all(|c| DEST_ORDER.contains(&c))
+        && DEST_ORDER
+            .iter()
+            .all(|register| dest.matches(*register).count() <= 1);
+    if !is_valid {
+        return dest.to_string();
+    }
+    DEST_ORDER
+        .iter()
+        .filter(|register| dest.contains(**register))
+        .collect()
+}
+
+// `//`の直後に空白を置く。`////`のような区切り線と、字下げしたコメントはそのまま
+fn normalize_comment(comment: &str) -> String {
+    let text = comment[COMMENT_TOKEN.len()..].trim_end();
+    match text.chars().next() {
+        Some(c) if !c.is_whitespace() && c != '/' => format!("{} {}", COMMENT_TOKEN, text),
+        _ if text.trim().is_empty() => COMMENT_TOKEN.to_string(),
+        _ => format!("{}{}", COMMENT_TOKEN, text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let source = "\n\n//Sum 1..100\n.var   i\n.string  msg  \"a  //  b\"\n(LOOP)\n@i // i\nDM=M+1 //next\n\n\n\
+                      // done?\n@100\nD=D-A\n1:\n  @LOOP\n  D;JNE    //loop\n.macro INC x\n@%x\nM=M+1\n.endm\n  INC   i\n\n";
+        let expected = "// Sum 1..100\n.var i\n.string msg \"a  //  b\"\n(LOOP)\n    @i     // i\n    MD=M+1 // next\n\n\
+                        \x20   // done?\n    @100\n    D=D-A\n1:\n    @LOOP\n    D;JNE // loop\n.macro INC x\n    @%x\n    M=M+1\n.endm\n    INC i\n";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize_dest("DM"), "MD");
+        assert_eq!(normalize_dest("DMA"), "AMD");
+        assert_eq!(normalize_dest("MM"), "MM");
+        assert_eq!(normalize_comment("//////"), "//////");
+        assert_eq!(normalize_comment("//    @x  "), "//    @x");
+        assert_eq!(normalize_comment("//  "), "//");
+        assert_eq!(format(".data  t 1,  ' '"), ".data t 1, ' '\n");
+        assert_eq!(format(""), "");
+    }
+
+    #[test]
+    fn test_format_spaced_c_instruction() {
+        assert_eq!(format("DM = M + 1\n"), "    MD=M+1\n");
+        assert_eq!(format("  D ; JGT // x\n"), "    D;JGT // x\n");
+    }
+
+    #[test]
+    fn test_format_crlf() {
+        let source = "(LOOP)\r\n@i   //i\r\n\r\n\r\nDM=M+1\r\n";
+        assert_eq!(
+            format(source),
+            "(LOOP)\r\n    @i // i\r\n\r\n    MD=M+1\r\n"
+        );
+        assert_eq!(format("@i\n@j\r\n"), "    @i\n    @j\n");
+    }
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!(first_difference("@i\n@j\n", "@i\n    @j\n"), 2);
+        //最後の改行がない、または改行の種類だけが違う場合もその行を指す
+        assert_eq!(first_difference("@i\n@j", "@i\n@j\n"), 2);
+        assert_eq!(first_difference("@i\r\n@j\n", "@i\n@j\n"), 1);
+        assert_eq!(first_difference("@i\n\n", "@i\n"), 2);
+        assert_eq!(first_difference("", "\n"), 1);
+    }
+}
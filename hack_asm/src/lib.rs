@@ -0,0 +1,120 @@
+use parser::Location;
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use symbol_table::SymbolTable;
+
+/// Hackアセンブリをコンパイル時にアセンブルして`&[u16]`に展開する
+///
+/// 空白で区切られたトークン列を1つの命令として扱う。アセンブルに失敗した場合は
+/// 該当する命令の位置でコンパイルエラーになる。
+///
+/// ```ignore
+/// const PROG: &[u16] = hack_asm! {
+///     @2 D=A
+///     @3 D=D+A
+///     @0 M=D
+/// };
+/// ```
+#[proc_macro]
+pub fn hack_asm(input: TokenStream) -> TokenStream {
+    let instructions = instructions(input);
+    let source = instructions
+        .iter()
+        .map(|(instruction, _)| instruction.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match assembler::assemble_source(&source, &mut SymbolTable::new()) {
+        Ok(words) => {
+            let words: Vec<String> = words.iter().map(|word| format!("{:#06X}u16", word)).collect();
+            format!("&[{}]", words.join(", ")).parse().unwrap()
+        }
+        Err(e) => match e.downcast_ref::<Location>() {
+            // 行番号は命令の並び順に対応するので、元の命令のトークン位置でエラーにする
+            Some(location) => {
+                let message = e.chain().skip(1).map(|cause| cause.to_string());
+                let span = instructions
+                    .get(location.line - 1)
+                    .map_or(Span::call_site(), |(_, span)| *span);
+                compile_error(&message.collect::<Vec<_>>().join(": "), span)
+            }
+            None => compile_error(&format!("{:#}", e), Span::call_site()),
+        },
+    }
+}
+
+// 隙間なく連続するトークンを1つの命令にまとめ、命令の文字列と先頭トークンの位置を返す。
+// ただしディレクティブ(`.macro`など)とマクロ呼び出しは同じ行の残りのトークンを引数として含める。
+fn instructions(input: TokenStream) -> Vec<(String, Span)> {
+    let mut instructions: Vec<(String, Span)> = Vec::new();
+    let mut previous_end: Option<Span> = None;
+    // 命令が識別子1つだけからなるか(マクロ呼び出し)
+    let mut is_single_ident = false;
+    // 命令が同じ行の残りのトークンを含めているか
+    let mut takes_rest_of_line = false;
+
+    for token in input {
+        let span = token.span();
+        let is_same_line = previous_end.is_some_and(|end| end.line() == span.start().line());
+        let is_adjacent = previous_end.is_some_and(|end| {
+            end.line() == span.start().line() && end.column() == span.start().column()
+        });
+        match instructions.last_mut() {
+            Some((instruction, _)) if is_adjacent => {
+                instruction.push_str(&token_text(&token));
+                is_single_ident = false;
+            }
+            Some((instruction, _))
+                if is_same_line
+                    && (takes_rest_of_line || is_single_ident || instruction.starts_with('.')) =>
+            {
+                instruction.push(' ');
+                instruction.push_str(&token_text(&token));
+                takes_rest_of_line = true;
+            }
+            _ => {
+                instructions.push((token_text(&token), span));
+                is_single_ident = matches!(token, TokenTree::Ident(_));
+                takes_rest_of_line = false;
+            }
+        }
+        previous_end = Some(span.end());
+    }
+
+    instructions
+}
+
+fn token_text(token: &TokenTree) -> String {
+    match token {
+        TokenTree::Group(group) => {
+            let (open, close) = match group.delimiter() {
+                Delimiter::Parenthesis => ("(", ")"),
+                Delimiter::Brace => ("{", "}"),
+                Delimiter::Bracket => ("[", "]"),
+                Delimiter::None => ("", ""),
+            };
+            let inner: String = group.stream().into_iter().map(|t| token_text(&t)).collect();
+            format!("{}{}{}", open, inner, close)
+        }
+        TokenTree::Ident(ident) => ident.to_string(),
+        TokenTree::Punct(punct) => punct.as_char().to_string(),
+        TokenTree::Literal(literal) => literal.to_string(),
+    }
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    let mut arguments = Group::new(
+        Delimiter::Parenthesis,
+        TokenStream::from(TokenTree::Literal(message)),
+    );
+    arguments.set_span(span);
+
+    TokenStream::from_iter([
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(arguments),
+    ])
+}
@@ -0,0 +1,70 @@
+use hack_asm::hack_asm;
+
+const ADD: &[u16] = hack_asm! { @2 D=A @3 D=D+A @0 M=D };
+
+const LOOP: &[u16] = hack_asm! {
+    // i = 0
+    @i
+    M=0
+(LOOP)
+    @i
+    M=M+1
+    @LOOP
+    0;JMP
+};
+
+#[test]
+fn test_hack_asm() {
+    assert_eq!(ADD, &[2, 0xEC10, 3, 0xE090, 0, 0xE308]);
+}
+
+#[test]
+fn test_hack_asm_label_and_variable() {
+    assert_eq!(LOOP, &[16, 0xEA88, 16, 0xFDC8, 2, 0xEA87]);
+}
+
+#[test]
+fn test_hack_asm_empty() {
+    let empty: &[u16] = hack_asm! {};
+    assert!(empty.is_empty());
+}
+
+const MACRO: &[u16] = hack_asm! {
+    .macro INC addr
+        @%addr
+        M=M+1
+    .endm
+    .macro WAIT addr, value
+    (LOOP)
+        @%addr
+        D=M
+        @%value
+        D=D-A
+        @LOOP
+        D;JNE
+    .endm
+    INC i
+    WAIT KBD, 65
+    INC i
+};
+
+#[test]
+fn test_hack_asm_macro() {
+    assert_eq!(
+        MACRO,
+        &[16, 0xFDC8, 24576, 0xFC10, 65, 0xE4D0, 2, 0xE305, 16, 0xFDC8]
+    );
+}
+
+#[test]
+fn test_hack_asm_literals() {
+    let words: &[u16] = hack_asm! { @0x4000 @0b1010 @'A' @SCREEN+32 };
+    assert_eq!(words, &[0x4000, 10, 65, 16416]);
+}
+
+// 誤った命令は、その命令のトークンの位置でコンパイルエラーになる
+#[test]
+fn test_hack_asm_compile_fail() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
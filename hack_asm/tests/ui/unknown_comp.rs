@@ -0,0 +1,13 @@
+use hack_asm::hack_asm;
+
+const PROG: &[u16] = hack_asm! {
+    @2
+    D=A
+    D=X
+    @0
+    M=D
+};
+
+fn main() {
+    let _ = PROG;
+}
@@ -0,0 +1,10 @@
+use hack_asm::hack_asm;
+
+const PROG: &[u16] = hack_asm! {
+    @1
+    .unknown x
+};
+
+fn main() {
+    let _ = PROG;
+}
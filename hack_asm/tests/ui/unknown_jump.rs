@@ -0,0 +1,7 @@
+use hack_asm::hack_asm;
+
+const PROG: &[u16] = hack_asm! { @LOOP 0;JUMP (LOOP) };
+
+fn main() {
+    let _ = PROG;
+}
@@ -0,0 +1,545 @@
+//! メッセージの英語と日本語の対訳
+//!
+//! 1つのキーに英語と日本語の書式を並べる。書式の引数は`{0}`、`{1:?}`のように番号で指定し、
+//! 語順の違う日本語でも同じ順に引数を渡せるようにする。
+
+/// キーと引数から、現在の言語のメッセージを作る
+#[macro_export]
+macro_rules! tr {
+    (@ $english:literal, $japanese:literal $(, $arg:expr)*) => {
+        match $crate::language() {
+            $crate::Language::English => format!($english $(, $arg)*),
+            $crate::Language::Japanese => format!($japanese $(, $arg)*),
+        }
+    };
+
+    // 共通
+    (warning) => { $crate::tr!(@ "warning", "警告") };
+    (error) => { $crate::tr!(@ "error", "エラー") };
+    (line $(, $arg:expr)*) => { $crate::tr!(@ "line {0}", "{0}行目" $(, $arg)*) };
+    (in_expansion $(, $arg:expr)*) => {
+        $crate::tr!(@ " (in expansion of macro {0} at {1})", " (マクロ{0}の展開中、{1})" $(, $arg)*)
+    };
+    (cannot_open $(, $arg:expr)*) => { $crate::tr!(@ "cannot open {0}", "{0}を開けません" $(, $arg)*) };
+    (cannot_read $(, $arg:expr)*) => { $crate::tr!(@ "cannot read {0}", "{0}を読み込めません" $(, $arg)*) };
+    (cannot_write $(, $arg:expr)*) => { $crate::tr!(@ "cannot write {0}", "{0}に書き込めません" $(, $arg)*) };
+    (unknown_language $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown language {0:?} (expected en or ja)", "不明な言語です: {0:?}(enかjaのいずれか)" $(, $arg)*)
+    };
+
+    // シンボルの種別
+    (kind_predefined) => { $crate::tr!(@ "predefined symbol", "定義済みシンボル") };
+    (kind_label) => { $crate::tr!(@ "label", "ラベル") };
+    (kind_constant) => { $crate::tr!(@ "constant", "定数") };
+    (kind_variable) => { $crate::tr!(@ "variable", "変数") };
+    (already_defined $(, $arg:expr)*) => {
+        $crate::tr!(@ "symbol {0} is already defined as a {1}", "シンボル{0}は{1}として定義済みです" $(, $arg)*)
+    };
+
+    // 命令のニーモニック
+    (invalid_instruction $(, $arg:expr)*) => {
+        $crate::tr!(@ "parse instruction type error. instruction_value: {0:?}", "命令として解釈できません: {0:?}" $(, $arg)*)
+    };
+    (unknown_dest $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown dest mnemonic: {0:?}", "不明なdestのニーモニックです: {0:?}" $(, $arg)*)
+    };
+    (unknown_comp $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown comp mnemonic: {0:?}", "不明なcompのニーモニックです: {0:?}" $(, $arg)*)
+    };
+    (invalid_label_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid label name: {0:?}", "ラベル名が不正です: {0:?}" $(, $arg)*)
+    };
+    (unknown_jump $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown jump mnemonic: {0:?}", "不明なjumpのニーモニックです: {0:?}" $(, $arg)*)
+    };
+
+    // ディレクティブ
+    (unknown_directive $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown directive: {0:?}", "不明なディレクティブです: {0:?}" $(, $arg)*)
+    };
+    (requires_name_and_value $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} requires a name and a value", "{0}には名前と値が必要です" $(, $arg)*)
+    };
+    (requires_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} requires a name", "{0}には名前が必要です" $(, $arg)*)
+    };
+    (requires_address $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} requires an address", "{0}にはアドレスが必要です" $(, $arg)*)
+    };
+    (requires_fields $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} requires a name and fields separated by {1:?}", "{0}には名前と{1:?}で区切ったフィールドが必要です" $(, $arg)*)
+    };
+    (requires_values $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} requires a name and values separated by {1:?}", "{0}には名前と{1:?}で区切った値が必要です" $(, $arg)*)
+    };
+    (requires_string $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} requires a name and a quoted string", "{0}には名前とダブルクォートで囲んだ文字列が必要です" $(, $arg)*)
+    };
+    (expected_quoted_string $(, $arg:expr)*) => {
+        $crate::tr!(@ "expected a quoted string but found {0:?}", "ダブルクォートで囲んだ文字列が必要です: {0:?}" $(, $arg)*)
+    };
+    (unterminated_escape $(, $arg:expr)*) => {
+        $crate::tr!(@ "unterminated escape in string {0:?}", "文字列{0:?}のエスケープが途中で終わっています" $(, $arg)*)
+    };
+    (unescaped_quote $(, $arg:expr)*) => {
+        $crate::tr!(@ "unescaped quote in string {0:?}", "文字列{0:?}にエスケープしていないダブルクォートがあります" $(, $arg)*)
+    };
+    (string_not_ascii $(, $arg:expr)*) => {
+        $crate::tr!(@ "string must be ASCII: {0:?}", "文字列はASCIIでなければなりません: {0:?}" $(, $arg)*)
+    };
+    (invalid_constant_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid constant name: {0:?}", "定数名が不正です: {0:?}" $(, $arg)*)
+    };
+    (invalid_variable_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid variable name: {0:?}", "変数名が不正です: {0:?}" $(, $arg)*)
+    };
+    (invalid_record_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid record name: {0:?}", "レコード名が不正です: {0:?}" $(, $arg)*)
+    };
+    (invalid_field_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid field name: {0:?}", "フィールド名が不正です: {0:?}" $(, $arg)*)
+    };
+    (field_size $(, $arg:expr)*) => {
+        $crate::tr!(@ "size of field {0} must be at least 1", "フィールド{0}の大きさは1以上でなければなりません" $(, $arg)*)
+    };
+    (record_too_large $(, $arg:expr)*) => {
+        $crate::tr!(@ "record {0} is too large", "レコード{0}が大きすぎます" $(, $arg)*)
+    };
+    (data_out_of_range $(, $arg:expr)*) => {
+        $crate::tr!(@ "value {0} is out of range (-32768 to 65535)", "値{0}が範囲外です(-32768から65535まで)" $(, $arg)*)
+    };
+
+    // 式
+    (undefined_symbol $(, $arg:expr)*) => {
+        $crate::tr!(@ "undefined symbol {0} in expression", "式の中のシンボル{0}が定義されていません" $(, $arg)*)
+    };
+    (current_address_not_allowed) => {
+        $crate::tr!(@ "$ cannot be used here", "ここでは$を使えません")
+    };
+    (local_label_not_found_after $(, $arg:expr)*) => {
+        $crate::tr!(@ "local label {0}: not found after this instruction", "この命令の後ろにローカルラベル{0}:がありません" $(, $arg)*)
+    };
+    (local_label_not_found_before $(, $arg:expr)*) => {
+        $crate::tr!(@ "local label {0}: not found before this instruction", "この命令の前にローカルラベル{0}:がありません" $(, $arg)*)
+    };
+    (division_by_zero) => { $crate::tr!(@ "division by zero", "0で割っています") };
+    (overflow $(, $arg:expr)*) => {
+        $crate::tr!(@ "overflow in {0} {1} {2}", "{0} {1} {2}でオーバーフローしました" $(, $arg)*)
+    };
+    (unexpected_token $(, $arg:expr)*) => {
+        $crate::tr!(@ "unexpected token in expression {0:?}", "式{0:?}に予期しない字句があります" $(, $arg)*)
+    };
+    (character_not_ascii $(, $arg:expr)*) => {
+        $crate::tr!(@ "character literal must be ASCII: {0:?}", "文字リテラルはASCIIでなければなりません: {0:?}" $(, $arg)*)
+    };
+    (invalid_character_literal $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid character literal in expression {0:?}", "式{0:?}の文字リテラルが不正です" $(, $arg)*)
+    };
+    (unexpected_character $(, $arg:expr)*) => {
+        $crate::tr!(@ "unexpected character {0:?} in expression {1:?}", "式{1:?}に予期しない文字{0:?}があります" $(, $arg)*)
+    };
+    (invalid_number $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid number {0:?}", "数値{0:?}が不正です" $(, $arg)*)
+    };
+    (unexpected_end_of_expression) => {
+        $crate::tr!(@ "unexpected end of expression", "式が途中で終わっています")
+    };
+    (missing_close_paren) => {
+        $crate::tr!(@ "missing ')' in expression", "式に')'がありません")
+    };
+    (unexpected_operator $(, $arg:expr)*) => {
+        $crate::tr!(@ "unexpected operator {0} in expression", "式に予期しない演算子{0}があります" $(, $arg)*)
+    };
+    (unexpected_close_paren) => {
+        $crate::tr!(@ "unexpected ')' in expression", "式に予期しない')'があります")
+    };
+
+    // インクルード
+    (include_cycle $(, $arg:expr)*) => {
+        $crate::tr!(@ "include cycle detected: {0}", "インクルードが循環しています: {0}" $(, $arg)*)
+    };
+    (malformed_include $(, $arg:expr)*) => {
+        $crate::tr!(@ "expected {0} \"file\" but found {1:?}", "{0} \"file\"の形式で書いてください: {1:?}" $(, $arg)*)
+    };
+    (include_not_found $(, $arg:expr)*) => {
+        $crate::tr!(@ "include file {0:?} not found", "インクルードするファイル{0:?}が見つかりません" $(, $arg)*)
+    };
+
+    // マクロ
+    (duplicate_macro $(, $arg:expr)*) => {
+        $crate::tr!(@ "macro {0} is already defined", "マクロ{0}は定義済みです" $(, $arg)*)
+    };
+    (nested_macro $(, $arg:expr)*) => {
+        $crate::tr!(@ "nested macro definition in macro {0}", "マクロ{0}の中でマクロを定義しています" $(, $arg)*)
+    };
+    (missing_end_macro $(, $arg:expr)*) => {
+        $crate::tr!(@ "macro {0} is missing {1}", "マクロ{0}に{1}がありません" $(, $arg)*)
+    };
+    (end_macro_without_macro $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} without {1}", "{1}のない{0}があります" $(, $arg)*)
+    };
+    (requires_macro_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} requires a macro name", "{0}にはマクロ名が必要です" $(, $arg)*)
+    };
+    (invalid_parameter $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid parameter name {0:?} in macro {1}", "マクロ{1}の引数名{0:?}が不正です" $(, $arg)*)
+    };
+    (duplicate_parameter $(, $arg:expr)*) => {
+        $crate::tr!(@ "duplicate parameter {0} in macro {1}", "マクロ{1}の引数{0}が重複しています" $(, $arg)*)
+    };
+    (macro_too_deep $(, $arg:expr)*) => {
+        $crate::tr!(@ "macro expansion of {0} is too deep (recursive macro?)", "マクロ{0}の展開が深すぎます(再帰したマクロ?)" $(, $arg)*)
+    };
+    (macro_arguments $(, $arg:expr)*) => {
+        $crate::tr!(@ "macro {0} takes {1} argument(s) but {2} were given", "マクロ{0}の引数は{1}個ですが、{2}個渡されました" $(, $arg)*)
+    };
+    (unknown_parameter $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown macro parameter {0}{1}", "不明なマクロの引数です: {0}{1}" $(, $arg)*)
+    };
+
+    // シンボルとRAMの割り当て
+    (invalid_variable_base) => {
+        $crate::tr!(@ "invalid variable base address", "変数を割り当て始めるアドレスが不正です")
+    };
+    (no_enclosing_label $(, $arg:expr)*) => {
+        $crate::tr!(@ "local label {0} has no enclosing global label", "ローカルラベル{0}の前にグローバルラベルがありません" $(, $arg)*)
+    };
+    (not_in_scope $(, $arg:expr)*) => {
+        $crate::tr!(@ "local label {0} is not defined in scope {1}", "ローカルラベル{0}はスコープ{1}に定義されていません" $(, $arg)*)
+    };
+    (not_in_scope_qualified $(, $arg:expr)*) => {
+        $crate::tr!(@ "local label {0} is not defined in scope {1} (use the fully qualified name: {2})", "ローカルラベル{0}はスコープ{1}に定義されていません(完全な名前{2}を使ってください)" $(, $arg)*)
+    };
+    (value_out_of_range $(, $arg:expr)*) => {
+        $crate::tr!(@ "value {0} is out of range (0 to {1})", "値{0}が範囲外です(0から{1}まで)" $(, $arg)*)
+    };
+    (undeclared_symbol $(, $arg:expr)*) => {
+        $crate::tr!(@ "undeclared symbol {0} (declare it with .var)", "シンボル{0}が宣言されていません(.varで宣言してください)" $(, $arg)*)
+    };
+    (undeclared_symbol_similar $(, $arg:expr)*) => {
+        $crate::tr!(@ "undeclared symbol {0} (did you mean {1}?)", "シンボル{0}が宣言されていません({1}の間違いですか?)" $(, $arg)*)
+    };
+    (implicit_variable $(, $arg:expr)*) => {
+        $crate::tr!(@ "symbol {0} is not declared; allocated as a variable at RAM[{1}]", "シンボル{0}が宣言されていないため、変数としてRAM[{1}]に割り当てました" $(, $arg)*)
+    };
+    (used_once_similar $(, $arg:expr)*) => {
+        $crate::tr!(@ " (used only once; did you mean {0}?)", "(1回しか使われていません。{0}の間違いですか?)" $(, $arg)*)
+    };
+    (ram_address_out_of_range $(, $arg:expr)*) => {
+        $crate::tr!(@ "RAM address {0} is out of range (0 to {1})", "RAMアドレス{0}が範囲外です(0から{1}まで)" $(, $arg)*)
+    };
+    (variable_size $(, $arg:expr)*) => {
+        $crate::tr!(@ "size of variable {0} must be at least 1", "変数{0}の大きさは1以上でなければなりません" $(, $arg)*)
+    };
+    (not_enough_ram $(, $arg:expr)*) => {
+        $crate::tr!(@ "not enough RAM for variable {0} ({1} word(s) from RAM[{2}])", "変数{0}を割り当てるRAMが足りません(RAM[{2}]から{1}ワード)" $(, $arg)*)
+    };
+    (rom_overflow $(, $arg:expr)*) => {
+        $crate::tr!(@ "program does not fit in ROM ({0} words, at most {1})", "プログラムがROMに収まりません({0}ワード、最大{1}ワード)" $(, $arg)*)
+    };
+    (ram_address_reserved $(, $arg:expr)*) => {
+        $crate::tr!(@ "RAM address {0} is reserved (variables start at RAM[{1}])", "RAMアドレス{0}は予約されています(変数はRAM[{1}]から)" $(, $arg)*)
+    };
+    (variable_overlaps $(, $arg:expr)*) => {
+        $crate::tr!(@ "variable {0} at RAM[{1}] overlaps variable {2} at RAM[{3}..{4}]", "RAM[{1}]の変数{0}がRAM[{3}..{4}]の変数{2}と重なっています" $(, $arg)*)
+    };
+
+    // 使われていないシンボルの警告とリント
+    (unused_label $(, $arg:expr)*) => {
+        $crate::tr!(@ "label {0} is never referenced", "ラベル{0}はどこからも参照されていません" $(, $arg)*)
+    };
+    (write_only_variable $(, $arg:expr)*) => {
+        $crate::tr!(@ "variable {0} is written but never read", "変数{0}は書き込まれるだけで読まれていません" $(, $arg)*)
+    };
+    (unused_variable $(, $arg:expr)*) => {
+        $crate::tr!(@ "variable {0} is never used", "変数{0}は使われていません" $(, $arg)*)
+    };
+    (single_use_variable $(, $arg:expr)*) => {
+        $crate::tr!(@ "variable {0} is used only once", "変数{0}は1回しか使われていません" $(, $arg)*)
+    };
+    (memory_in_jump $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} uses M in a jumping instruction; A is both the jump target and the memory address", "{0}はジャンプする命令でMを使っています。Aがジャンプ先とメモリのアドレスを兼ねます" $(, $arg)*)
+    };
+    (a_write_in_jump $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} writes A in a jumping instruction; the jump uses A before the write", "{0}はジャンプする命令でAに書き込んでいます。ジャンプ先には書き込む前のAが使われます" $(, $arg)*)
+    };
+    (unreachable_code $(, $arg:expr)*) => {
+        $crate::tr!(@ "unreachable code at ROM[{0}..{1}] after an unconditional jump (missing label?)", "常にジャンプする命令の後ろのROM[{0}..{1}]のコードは実行されません(ラベルの付け忘れ?)" $(, $arg)*)
+    };
+    (jump_to_data $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0} jumps to {1} ({2}) instead of a label", "{0}はラベルではなく{1}({2})へジャンプします" $(, $arg)*)
+    };
+
+    // 警告の規則
+    (unknown_lint_level $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown lint level {0:?} (expected allow, warn or deny)", "不明な警告の水準です: {0:?}(allow、warn、denyのいずれか)" $(, $arg)*)
+    };
+    (unknown_lint_rule $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown lint rule {0:?}", "不明な警告の規則です: {0:?}" $(, $arg)*)
+    };
+    (malformed_pragma $(, $arg:expr)*) => {
+        $crate::tr!(@ "expected hackasm:LEVEL(RULE, ...) but found {0:?}", "hackasm:LEVEL(RULE, ...)の形式で書いてください: {0:?}" $(, $arg)*)
+    };
+    (invalid_lints_config) => {
+        $crate::tr!(@ "invalid [lints] in config file", "設定ファイルの[lints]が不正です")
+    };
+    (aborting $(, $arg:expr)*) => {
+        $crate::tr!(@ "aborting due to {0} lint error(s)", "警告の規則による{0}件のエラーのため中止しました" $(, $arg)*)
+    };
+
+    // エラー番号
+    (unknown_error_code $(, $arg:expr)*) => {
+        $crate::tr!(@ "unknown error code {0:?}", "不明なエラー番号です: {0:?}" $(, $arg)*)
+    };
+    (incorrect) => { $crate::tr!(@ "Incorrect", "誤った例") };
+    (correct) => { $crate::tr!(@ "Correct", "正しい例") };
+
+    // エラー番号の短い説明
+    (title_invalid_instruction) => { $crate::tr!(@ "invalid instruction", "不正な命令") };
+    (title_unknown_dest) => { $crate::tr!(@ "unknown dest mnemonic", "不明なdestのニーモニック") };
+    (title_unknown_comp) => { $crate::tr!(@ "unknown comp mnemonic", "不明なcompのニーモニック") };
+    (title_unknown_jump) => { $crate::tr!(@ "unknown jump mnemonic", "不明なjumpのニーモニック") };
+    (title_duplicate_symbol) => { $crate::tr!(@ "symbol is already defined", "定義済みのシンボル") };
+    (title_unknown_directive) => { $crate::tr!(@ "unknown directive", "不明なディレクティブ") };
+    (title_malformed_directive) => { $crate::tr!(@ "malformed directive", "ディレクティブの書式の誤り") };
+    (title_invalid_string) => { $crate::tr!(@ "invalid string literal", "不正な文字列リテラル") };
+    (title_invalid_expression) => { $crate::tr!(@ "invalid expression", "不正な式") };
+    (title_invalid_number) => { $crate::tr!(@ "invalid number", "不正な数値") };
+    (title_undefined_symbol) => { $crate::tr!(@ "undefined symbol in expression", "式の中の未定義のシンボル") };
+    (title_current_address_not_allowed) => { $crate::tr!(@ "$ cannot be used here", "ここでは$を使えない") };
+    (title_local_label_not_found) => { $crate::tr!(@ "local label not found", "ローカルラベルが見つからない") };
+    (title_arithmetic_error) => { $crate::tr!(@ "arithmetic error in expression", "式の計算の誤り") };
+    (title_include_not_found) => { $crate::tr!(@ "include file not found", "インクルードするファイルが見つからない") };
+    (title_include_cycle) => { $crate::tr!(@ "include cycle", "インクルードの循環") };
+    (title_malformed_include) => { $crate::tr!(@ "malformed .include", ".includeの書式の誤り") };
+    (title_duplicate_macro) => { $crate::tr!(@ "macro is already defined", "定義済みのマクロ") };
+    (title_malformed_macro) => { $crate::tr!(@ "malformed macro definition", "マクロの定義の誤り") };
+    (title_macro_arguments) => { $crate::tr!(@ "wrong macro arguments", "マクロの引数の誤り") };
+    (title_macro_too_deep) => { $crate::tr!(@ "macro expansion is too deep", "マクロの展開が深すぎる") };
+    (title_rom_overflow) => { $crate::tr!(@ "program does not fit in ROM", "プログラムがROMに収まらない") };
+    (title_invalid_symbol_name) => { $crate::tr!(@ "invalid symbol name", "不正なシンボル名") };
+    (title_invalid_size) => { $crate::tr!(@ "invalid size", "不正な大きさ") };
+    (title_value_out_of_range) => { $crate::tr!(@ "value is out of range", "範囲外の値") };
+    (title_label_out_of_scope) => { $crate::tr!(@ "local label is not in scope", "スコープにないローカルラベル") };
+    (title_invalid_ram_address) => { $crate::tr!(@ "invalid RAM address for variables", "変数に使えないRAMアドレス") };
+    (title_out_of_ram) => { $crate::tr!(@ "not enough RAM for variables", "変数を割り当てるRAMの不足") };
+    (title_variable_overlap) => { $crate::tr!(@ "variables overlap", "変数の領域の重なり") };
+    (title_undeclared_symbol) => { $crate::tr!(@ "undeclared symbol", "宣言されていないシンボル") };
+    (title_malformed_define) => { $crate::tr!(@ "malformed -D", "-Dの書式の誤り") };
+
+    // `--explain`の説明。誤った例と正しい例はerror_codeクレートに置く
+    (explain_invalid_instruction) => {
+        $crate::tr!(@ "Every line must be an A-instruction (@value), a C-instruction (dest=comp;jump),
+a label declaration ((LABEL)), a local label (1:), a directive (.equ etc.) or a comment.",
+            "各行はA命令(@value)、C命令(dest=comp;jump)、ラベルの宣言((LABEL))、
+ローカルラベル(1:)、ディレクティブ(.equなど)、コメントのいずれかでなければなりません。")
+    };
+    (explain_unknown_dest) => {
+        $crate::tr!(@ "The dest part of a C-instruction names the registers that receive the result.
+It is a combination of A, D and M, each used at most once.",
+            "C命令のdestは計算結果を書き込むレジスタで、A、D、Mをそれぞれ1回まで組み合わせて書きます。")
+    };
+    (explain_unknown_comp) => {
+        $crate::tr!(@ "The comp part of a C-instruction must be one of the 28 computations of the Hack ALU.
+The ALU cannot multiply, divide or add a constant other than 1, and an instruction
+cannot use A and M together.",
+            "C命令のcompはHackのALUの28種類の計算のいずれかでなければなりません。
+ALUは掛け算や割り算、1以外の定数の足し算ができず、1つの命令でAとMを一緒に使えません。")
+    };
+    (explain_unknown_jump) => {
+        $crate::tr!(@ "The jump part of a C-instruction must be one of JGT, JEQ, JGE, JLT, JNE, JLE or JMP.",
+            "C命令のjumpはJGT、JEQ、JGE、JLT、JNE、JLE、JMPのいずれかでなければなりません。")
+    };
+    (explain_duplicate_symbol) => {
+        $crate::tr!(@ "A label, a constant or a variable cannot be defined twice, and it cannot have the
+same name as another symbol or a predefined symbol such as SCREEN.",
+            "ラベル、定数、変数は再定義できません。また、他のシンボルやSCREENなどの定義済みシンボルと
+同じ名前は使えません。")
+    };
+    (explain_unknown_directive) => {
+        $crate::tr!(@ "Lines starting with . are assembler directives. The supported directives are
+.equ, .define, .var, .org, .record, .data, .string, .include, .macro and .endm.",
+            ".で始まる行はアセンブラのディレクティブです。使えるディレクティブは
+.equ、.define、.var、.org、.record、.data、.string、.include、.macro、.endmです。")
+    };
+    (explain_malformed_directive) => {
+        $crate::tr!(@ "A directive is missing its name or its arguments.",
+            "ディレクティブの名前か引数が足りません。")
+    };
+    (explain_invalid_string) => {
+        $crate::tr!(@ "A string must be ASCII and enclosed in double quotes. Use \\\" for a quote and \\\\
+for a backslash.",
+            "文字列はASCIIで、ダブルクォートで囲まなければなりません。ダブルクォートは\\\"、
+バックスラッシュは\\\\と書きます。")
+    };
+    (explain_invalid_expression) => {
+        $crate::tr!(@ "An expression is built from numbers, symbols, $, 'c' character literals, unary -,
+the operators + - * / & | << >> and parentheses.",
+            "式には数値、シンボル、$、'c'のような文字リテラル、単項の-、演算子+ - * / & | << >>と
+括弧を使えます。")
+    };
+    (explain_invalid_number) => {
+        $crate::tr!(@ "A number is decimal, hexadecimal with 0x or binary with 0b.",
+            "数値は10進数、0xを付けた16進数、0bを付けた2進数のいずれかで書きます。")
+    };
+    (explain_undefined_symbol) => {
+        $crate::tr!(@ "Symbols in an expression must be defined. Directive arguments can only use
+symbols defined on earlier lines.",
+            "式の中のシンボルは定義されていなければなりません。ディレクティブの引数には、
+それより前の行で定義したシンボルしか使えません。")
+    };
+    (explain_current_address_not_allowed) => {
+        $crate::tr!(@ "$ is the ROM address of the current instruction, so it can only be used in the
+source. It cannot be used where there is no current instruction, such as in -D.",
+            "$は現在の命令のROMアドレスなので、ソースの中でしか使えません。
+-Dのように現在の命令がない場所では使えません。")
+    };
+    (explain_local_label_not_found) => {
+        $crate::tr!(@ "A local label reference 1f refers to the next 1: after the instruction, and 1b to
+the previous 1: before it.",
+            "ローカルラベルの参照1fは命令より後ろの最初の1:を、1bは命令より前の最も近い1:を指します。")
+    };
+    (explain_arithmetic_error) => {
+        $crate::tr!(@ "The expression divides by zero or its value does not fit in 64 bits.",
+            "式が0で割っているか、値が64ビットに収まりません。")
+    };
+    (explain_include_not_found) => {
+        $crate::tr!(@ "The file of .include is searched in the directory of the including file and then
+in the directories given with -I.",
+            ".includeのファイルは、インクルードする側のファイルのディレクトリ、
+-Iで指定したディレクトリの順に探します。")
+    };
+    (explain_include_cycle) => {
+        $crate::tr!(@ "A file includes itself directly or through other files.",
+            "ファイルが直接または他のファイルを通して自分自身をインクルードしています。")
+    };
+    (explain_malformed_include) => {
+        $crate::tr!(@ "The file name of .include must be enclosed in double quotes.",
+            ".includeのファイル名はダブルクォートで囲まなければなりません。")
+    };
+    (explain_duplicate_macro) => {
+        $crate::tr!(@ "Each macro name can be defined only once.",
+            "同じ名前のマクロは1回しか定義できません。")
+    };
+    (explain_malformed_macro) => {
+        $crate::tr!(@ "A macro definition starts with .macro NAME params and ends with .endm. Macros
+cannot be nested and parameter names must be unique.",
+            "マクロの定義は.macro NAME 引数で始まり、.endmで終わります。マクロの中でマクロは定義できず、
+引数名は重複できません。")
+    };
+    (explain_macro_arguments) => {
+        $crate::tr!(@ "A macro call must pass one argument for each parameter, and the body can only use
+its own parameters.",
+            "マクロの呼び出しでは引数ごとに1つの値を渡さなければならず、本体では自分の引数しか使えません。")
+    };
+    (explain_macro_too_deep) => {
+        $crate::tr!(@ "Macro calls inside macros are expanded recursively up to a fixed depth. A macro
+that calls itself never stops expanding.",
+            "マクロの中のマクロの呼び出しは決まった深さまで再帰的に展開されます。
+自分自身を呼び出すマクロは展開が終わりません。")
+    };
+    (explain_rom_overflow) => {
+        $crate::tr!(@ "The instructions, and with --init the code that initializes .data and .string,
+must fit in the 32768 words of ROM.",
+            "命令と、--initを指定した場合は.dataと.stringを初期化するコードが、
+ROMの32768ワードに収まらなければなりません。")
+    };
+    (explain_invalid_symbol_name) => {
+        $crate::tr!(@ "A symbol name starts with a character other than a digit and consists of letters,
+digits and _.$:. The register names A, D and M cannot be used as symbol names.",
+            "シンボル名は数字以外で始まり、英数字と_.$:からなります。
+レジスタの名前A、D、Mはシンボル名に使えません。")
+    };
+    (explain_invalid_size) => {
+        $crate::tr!(@ "The size of a variable or a record field must be at least 1, and a record must fit
+in the 15-bit range of an A-instruction.",
+            "変数やレコードのフィールドの大きさは1以上でなければならず、
+レコード全体はA命令の15ビットの範囲に収まらなければなりません。")
+    };
+    (explain_value_out_of_range) => {
+        $crate::tr!(@ "An A-instruction can load values from 0 to 32767. Values of .data must be from
+-32768 to 65535, and addresses given with -D must fit in 16 bits.",
+            "A命令で読み込める値は0から32767までです。.dataの値は-32768から65535まで、
+-Dで指定するアドレスは16ビットに収まらなければなりません。")
+    };
+    (explain_label_out_of_scope) => {
+        $crate::tr!(@ "A local label such as .loop belongs to the global label before it. It must follow a
+global label, and a local label of another scope must be referenced by its full
+name such as MAIN.loop.",
+            ".loopのようなローカルラベルは直前のグローバルラベルに属します。グローバルラベルの後ろに書く必要があり、
+他のスコープのローカルラベルはMAIN.loopのような完全な名前で参照します。")
+    };
+    (explain_invalid_ram_address) => {
+        $crate::tr!(@ "Variables are allocated below the screen (0x4000). .org cannot go back before
+RAM[16], or before the address given with --variable-base, so that variables do not
+overwrite R0 to R15.",
+            "変数はスクリーン(0x4000)より前に割り当てます。変数でR0〜R15を上書きしないよう、
+.orgでRAM[16]や--variable-baseで指定したアドレスより前には戻れません。")
+    };
+    (explain_out_of_ram) => {
+        $crate::tr!(@ "The variables do not fit in the RAM below the screen (0x4000).",
+            "変数がスクリーン(0x4000)より前のRAMに収まりません。")
+    };
+    (explain_variable_overlap) => {
+        $crate::tr!(@ "After .org moves the allocation address back, a new variable overlaps the RAM of a
+variable allocated before.",
+            ".orgで割り当てるアドレスを戻したため、新しい変数がそれより前に割り当てた変数のRAMと重なっています。")
+    };
+    (explain_undeclared_symbol) => {
+        $crate::tr!(@ "With --strict, every variable must be declared with .var, so a misspelled symbol
+is reported instead of being allocated as a new variable.",
+            "--strictを指定すると変数は.varで宣言しなければならず、
+綴りを間違えたシンボルは新しい変数として割り当てられずにエラーになります。")
+    };
+    (explain_malformed_define) => {
+        $crate::tr!(@ "-D defines a symbol as NAME=ADDRESS. The address can be an expression without
+symbols.",
+            "-DはNAME=ADDRESSの形式でシンボルを定義します。アドレスにはシンボルを含まない式を使えます。")
+    };
+
+    // 設定と出力
+    (invalid_config_file $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid config file {0}", "設定ファイル{0}が不正です" $(, $arg)*)
+    };
+    (expected_define $(, $arg:expr)*) => {
+        $crate::tr!(@ "expected NAME=ADDRESS but found {0:?}", "NAME=ADDRESSの形式で指定してください: {0:?}" $(, $arg)*)
+    };
+    (invalid_define_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "invalid symbol name: {0:?}", "シンボル名が不正です: {0:?}" $(, $arg)*)
+    };
+    (define_out_of_range $(, $arg:expr)*) => {
+        $crate::tr!(@ "address {0} of {1} is out of range", "{1}のアドレス{0}が範囲外です" $(, $arg)*)
+    };
+    (define_out_of_range_max $(, $arg:expr)*) => {
+        $crate::tr!(@ "address {0} of {1} is out of range (0 to {2})", "{1}のアドレス{0}が範囲外です(0から{2}まで)" $(, $arg)*)
+    };
+    (constant_name_conflict $(, $arg:expr)*) => {
+        $crate::tr!(@ "label {0:?} conflicts with another label as constant name {1}", "ラベル{0:?}は定数名{1}が別のラベルと重なります" $(, $arg)*)
+    };
+    (reserved_constant_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "label {0:?} cannot be used as constant name {1}, which is reserved in the generated file", "ラベル{0:?}の定数名{1}は出力するファイルで使われているため使えません" $(, $arg)*)
+    };
+    (no_file_name $(, $arg:expr)*) => {
+        $crate::tr!(@ "cannot get the file name of {0:?}", "{0:?}のファイル名が分かりません" $(, $arg)*)
+    };
+    (no_output_directory $(, $arg:expr)*) => {
+        $crate::tr!(@ "cannot get the directory of {0:?}", "{0:?}のディレクトリが分かりません" $(, $arg)*)
+    };
+    (symbol_map_written $(, $arg:expr)*) => {
+        $crate::tr!(@ "Symbol map: {0}", "シンボルの一覧: {0}" $(, $arg)*)
+    };
+    (ram_image_written $(, $arg:expr)*) => {
+        $crate::tr!(@ "RAM image: {0}", "RAMの初期値: {0}" $(, $arg)*)
+    };
+    (assembled $(, $arg:expr)*) => {
+        $crate::tr!(@ "Assembled: {0}", "アセンブルしました: {0}" $(, $arg)*)
+    };
+
+    // 整形
+    (formatted $(, $arg:expr)*) => {
+        $crate::tr!(@ "Formatted: {0}", "整形しました: {0}" $(, $arg)*)
+    };
+    (not_formatted $(, $arg:expr)*) => {
+        $crate::tr!(@ "{0}:{1}: not formatted", "{0}:{1}: 整形されていません" $(, $arg)*)
+    };
+}
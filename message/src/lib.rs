@@ -0,0 +1,96 @@
+//! 利用者に表示するメッセージの言語の選択と、日本語と英語の対訳
+//!
+//! メッセージは`tr!(unknown_comp, v)`のようにキーと引数で作る。対訳はcatalogue.rsにまとめてある。
+
+use anyhow::{anyhow, Result};
+use std::{cell::Cell, env, str::FromStr};
+
+mod catalogue;
+
+// 言語を選ぶ環境変数。先にあるものを優先する
+const LOCALE_VARIABLES: [&str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+const JAPANESE_PREFIX: &str = "ja";
+
+/// メッセージの言語
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Japanese,
+}
+
+impl Language {
+    /// `ja_JP.UTF-8`のようなロケール名の言語。日本語以外は英語
+    pub fn from_locale(locale: &str) -> Self {
+        if locale.starts_with(JAPANESE_PREFIX) {
+            Language::Japanese
+        } else {
+            Language::English
+        }
+    }
+
+    /// 環境変数LC_ALL、LC_MESSAGES、LANGの順に見て言語を決める
+    pub fn from_env() -> Self {
+        LOCALE_VARIABLES
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|locale| !locale.is_empty())
+            .map(|locale| Language::from_locale(&locale))
+            .unwrap_or_default()
+    }
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "en" => Ok(Language::English),
+            "ja" => Ok(Language::Japanese),
+            _ => Err(anyhow!(tr!(unknown_language, s))),
+        }
+    }
+}
+
+thread_local! {
+    // スレッドごとに持つので、テストは並行に動かしても既定の英語のまま
+    static LANGUAGE: Cell<Language> = const { Cell::new(Language::English) };
+}
+
+/// 現在のスレッドで使う言語
+pub fn language() -> Language {
+    LANGUAGE.with(|language| language.get())
+}
+
+/// 現在のスレッドで使う言語を設定する
+pub fn set_language(language: Language) {
+    LANGUAGE.with(|current| current.set(language));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language() -> Result<()> {
+        assert_eq!(Language::from_locale("ja_JP.UTF-8"), Language::Japanese);
+        assert_eq!(Language::from_locale("en_US.UTF-8"), Language::English);
+        assert_eq!(Language::from_locale("C"), Language::English);
+        assert_eq!("JA".parse::<Language>()?, Language::Japanese);
+        assert!("fr".parse::<Language>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tr() {
+        assert_eq!(language(), Language::English);
+        assert_eq!(tr!(unknown_comp, "X"), "unknown comp mnemonic: \"X\"");
+        assert_eq!(tr!(division_by_zero), "division by zero");
+        set_language(Language::Japanese);
+        assert_eq!(tr!(unknown_comp, "X"), "不明なcompのニーモニックです: \"X\"");
+        assert_eq!(
+            tr!(macro_arguments, "INC", 1, 2),
+            "マクロINCの引数は1個ですが、2個渡されました"
+        );
+    }
+}
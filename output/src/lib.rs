@@ -0,0 +1,205 @@
+use anyhow::{bail, Result};
+use message::tr;
+use std::collections::HashSet;
+use symbol_table::{SymbolKind, SymbolTable};
+
+pub mod report;
+
+const WORDS_PER_LINE: usize = 8;
+// Rustのモジュールで定義する配列の名前
+const RUST_ROM_NAME: &str = "ROM";
+
+/// nand2tetris形式(1行に16桁の2進数文字列)
+pub fn hack(words: &[u16]) -> String {
+    words.iter().map(|word| format!("{:016b}\n", word)).collect()
+}
+
+/// RAMの初期値をhack形式で0番地から初期値のある最後のアドレスまで並べたもの。初期値のない番地は0とする
+pub fn ram_image(ram_data: &[(u16, u16)]) -> String {
+    let size = ram_data
+        .iter()
+        .map(|(address, _)| *address as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut words = vec![0; size];
+    for (address, value) in ram_data {
+        words[*address as usize] = *value;
+    }
+    hack(&words)
+}
+
+/// `static const uint16_t rom[]`を定義するCのヘッダファイル
+pub fn c_header(name: &str, words: &[u16], labels: &[(String, u16)]) -> Result<String> {
+    let guard = format!("{}_HACK_H", identifier(name));
+    let mut header = String::new();
+    header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+    header.push_str("#include <stdint.h>\n\n");
+    for (label, address) in constant_names(labels, &[&guard])? {
+        header.push_str(&format!("#define {} {}\n", label, address));
+    }
+    if !labels.is_empty() {
+        header.push('\n');
+    }
+    header.push_str("static const uint16_t rom[] = {\n");
+    header.push_str(&array_body(words));
+    header.push_str("};\n\n");
+    header.push_str(&format!("#endif /* {} */\n", guard));
+
+    Ok(header)
+}
+
+/// `pub const ROM: [u16; N]`を定義するRustのモジュール
+pub fn rust_module(words: &[u16], labels: &[(String, u16)]) -> Result<String> {
+    let mut module = String::new();
+    for (label, address) in constant_names(labels, &[RUST_ROM_NAME])? {
+        module.push_str(&format!("pub const {}: u16 = {};\n", label, address));
+    }
+    if !labels.is_empty() {
+        module.push('\n');
+    }
+    module.push_str(&format!(
+        "pub const {}: [u16; {}] = [\n",
+        RUST_ROM_NAME,
+        words.len()
+    ));
+    module.push_str(&array_body(words));
+    module.push_str("];\n");
+
+    Ok(module)
+}
+
+/// 定義済みシンボルを除くシンボルの種別・値・名前の一覧。2ワード以上の変数はワード数も示す
+pub fn symbol_map(symbol_table: &SymbolTable) -> String {
+    [
+        SymbolKind::Label,
+        SymbolKind::Constant,
+        SymbolKind::Variable,
+    ]
+    .into_iter()
+    .flat_map(|kind| {
+        symbol_table
+            .symbols(kind)
+            .into_iter()
+            .map(move |(symbol, value)| match symbol_table.size(&symbol) {
+                1 => format!("{:<10} {:>5} {}\n", kind, value, symbol),
+                size => format!("{:<10} {:>5} {} ({} words)\n", kind, value, symbol, size),
+            })
+    })
+    .collect()
+}
+
+fn array_body(words: &[u16]) -> String {
+    words
+        .chunks(WORDS_PER_LINE)
+        .map(|chunk| {
+            let line: Vec<String> = chunk.iter().map(|word| format!("0x{:04X},", word)).collect();
+            format!("    {}\n", line.join(" "))
+        })
+        .collect()
+}
+
+// ラベル名をC/Rustの定数名として使える形に変換する。変換後に名前が衝突する場合や、
+// インクルードガードなど出力するファイルで使う名前(reserved)と重なる場合はエラーとする。
+fn constant_names(labels: &[(String, u16)], reserved: &[&str]) -> Result<Vec<(String, u16)>> {
+    let mut names = HashSet::new();
+    let mut constants = Vec::new();
+    for (label, address) in labels {
+        let name = identifier(label);
+        if reserved.contains(&name.as_str()) {
+            bail!(tr!(reserved_constant_name, label, name));
+        }
+        if !names.insert(name.clone()) {
+            bail!(tr!(constant_name_conflict, label, name));
+        }
+        constants.push((name, *address));
+    }
+
+    Ok(constants)
+}
+
+fn identifier(v: &str) -> String {
+    let name: String = v
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_digit() => format!("_{}", name),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hack() {
+        assert_eq!(hack(&[40, 60893]), "0000000000101000\n1110110111011101\n");
+    }
+
+    #[test]
+    fn test_ram_image() {
+        assert_eq!(ram_image(&[]), "");
+        assert_eq!(
+            ram_image(&[(2, 65), (1, 0xFFFF)]),
+            "0000000000000000\n1111111111111111\n0000000001000001\n"
+        );
+    }
+
+    #[test]
+    fn test_c_header() -> Result<()> {
+        let labels = vec![("LOOP".to_string(), 1), ("ball.move$end".to_string(), 2)];
+        let header = c_header("Add", &[2, 60432, 3], &labels)?;
+        assert_eq!(
+            header,
+            "#ifndef ADD_HACK_H\n#define ADD_HACK_H\n\n#include <stdint.h>\n\n\
+             #define LOOP 1\n#define BALL_MOVE_END 2\n\n\
+             static const uint16_t rom[] = {\n    0x0002, 0xEC10, 0x0003,\n};\n\n\
+             #endif /* ADD_HACK_H */\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rust_module() -> Result<()> {
+        let words: Vec<u16> = (0..9).collect();
+        let module = rust_module(&words, &[("end".to_string(), 8)])?;
+        assert_eq!(
+            module,
+            "pub const END: u16 = 8;\n\n\
+             pub const ROM: [u16; 9] = [\n\
+             \x20   0x0000, 0x0001, 0x0002, 0x0003, 0x0004, 0x0005, 0x0006, 0x0007,\n\
+             \x20   0x0008,\n];\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbol_map() -> Result<()> {
+        let mut symbol_table = SymbolTable::new();
+        symbol_table.add_symbol("i", 16, SymbolKind::Variable)?;
+        symbol_table.add_variable("buffer", 17, 64)?;
+        symbol_table.add_symbol("WIDTH", 512, SymbolKind::Constant)?;
+        symbol_table.add_symbol("LOOP", 4, SymbolKind::Label)?;
+        symbol_table.add_symbol("END", 10, SymbolKind::Label)?;
+        assert_eq!(
+            symbol_map(&symbol_table),
+            "label          4 LOOP\nlabel         10 END\nconstant     512 WIDTH\nvariable      16 i\nvariable      17 buffer (64 words)\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_constant_name_conflict() {
+        let labels = vec![("a.b".to_string(), 1), ("A_B".to_string(), 2)];
+        assert!(rust_module(&[], &labels).is_err());
+
+        // 出力するファイル自体が使う名前とも重ならない
+        assert!(rust_module(&[], &[("rom".to_string(), 1)]).is_err());
+        assert!(c_header("Add", &[], &[("rom".to_string(), 1)]).is_ok());
+        assert!(c_header("Add", &[], &[("add_hack_h".to_string(), 1)]).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! CIやエディタで読み込むための、警告とエラーの機械可読な出力
+//!
+//! `--message-format=json`では1件ごとに1行のJSONを、`--sarif`ではSARIF 2.1.0のログを出力する。
+
+use message::tr;
+use parser::Location;
+use serde_json::{json, Value};
+use std::fmt;
+
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    // 人が読む出力に使う、現在の言語での名前
+    fn description(&self) -> String {
+        match self {
+            Severity::Error => tr!(error),
+            Severity::Warning => tr!(warning),
+        }
+    }
+}
+
+/// 出力する警告またはエラー
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub severity: Severity,
+    /// 警告の規則の名前またはエラー番号。番号のないエラーはNone
+    pub code: Option<String>,
+    pub message: String,
+    /// 位置。範囲が付いていれば列も出力する
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}: {}", self.severity.description(), self.message)?;
+        if let Some(code) = &self.code {
+            write!(f, " [{}]", code)?;
+        }
+        Ok(())
+    }
+}
+
+/// `--message-format=json`の1行分のJSON
+pub fn json(report: &Report) -> String {
+    let location = report.location.as_ref();
+    let span = location.and_then(|location| location.span.map(|span| (location.line, span)));
+    json!({
+        "code": report.code,
+        "severity": report.severity.as_str(),
+        "message": report.message,
+        "file": location.and_then(|location| location.file.as_deref()),
+        "line": location.map(|location| location.line),
+        "column": span.map(|(_, span)| span.start),
+        "span": span.map(|(line, span)| json!({
+            "line_start": line,
+            "column_start": span.start,
+            "line_end": line,
+            "column_end": span.end,
+        })),
+    })
+    .to_string()
+}
+
+/// SARIF 2.1.0のログ
+pub fn sarif(reports: &[Report], tool_name: &str, tool_version: &str) -> String {
+    let mut rules: Vec<&str> = reports
+        .iter()
+        .filter_map(|report| report.code.as_deref())
+        .collect();
+    rules.sort();
+    rules.dedup();
+    let results: Vec<Value> = reports.iter().map(sarif_result).collect();
+    let log = json!({
+        "version": SARIF_VERSION,
+        "$schema": SARIF_SCHEMA,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": tool_name,
+                    "version": tool_version,
+                    "rules": rules.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }],
+    });
+    format!("{:#}\n", log)
+}
+
+fn sarif_result(report: &Report) -> Value {
+    let mut result = json!({
+        "level": report.severity.as_str(),
+        "message": { "text": report.message },
+    });
+    if let Some(code) = &report.code {
+        result["ruleId"] = json!(code);
+    }
+    if let Some(location) = &report.location {
+        let mut region = json!({ "startLine": location.line });
+        if let Some(span) = location.span {
+            region["startColumn"] = json!(span.start);
+            region["endColumn"] = json!(span.end);
+        }
+        let mut physical_location = json!({ "region": region });
+        if let Some(file) = &location.file {
+            physical_location["artifactLocation"] = json!({ "uri": file });
+        }
+        result["locations"] = json!([{ "physicalLocation": physical_location }]);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::Span;
+
+    fn warning() -> Report {
+        Report {
+            severity: Severity::Warning,
+            code: Some("unused-label".to_string()),
+            message: "label END is never referenced".to_string(),
+            location: Some(Location {
+                file: Some("Main.asm".to_string()),
+                span: Some(Span { start: 3, end: 8 }),
+                ..Location::new(3)
+            }),
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            json(&warning()),
+            "{\"code\":\"unused-label\",\"column\":3,\"file\":\"Main.asm\",\"line\":3,\
+             \"message\":\"label END is never referenced\",\"severity\":\"warning\",\
+             \"span\":{\"column_end\":8,\"column_start\":3,\"line_end\":3,\"line_start\":3}}"
+        );
+        let error = Report {
+            severity: Severity::Error,
+            code: None,
+            message: "cannot read Main.asm".to_string(),
+            location: None,
+        };
+        assert_eq!(
+            json(&error),
+            "{\"code\":null,\"column\":null,\"file\":null,\"line\":null,\
+             \"message\":\"cannot read Main.asm\",\"severity\":\"error\",\"span\":null}"
+        );
+        assert_eq!(
+            warning().to_string(),
+            "Main.asm:3: warning: label END is never referenced [unused-label]"
+        );
+    }
+
+    #[test]
+    fn test_sarif() {
+        let log: Value =
+            serde_json::from_str(&sarif(&[warning()], "HackAssembler", "0.1.0")).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "unused-label");
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "unused-label");
+        assert_eq!(result["level"], "warning");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "Main.asm");
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(location["region"]["startColumn"], 3);
+    }
+}
@@ -0,0 +1,396 @@
+//! コメントや空白を含めてソースをそのまま保持する構文木
+//!
+//! `Parser`は命令の解釈に必要ない空白、空行、コメントを読み飛ばすため、解析結果からソースを書き戻せない。
+//! `SyntaxTree`はソースの各行を、空白とコメントを含むすべてのトークンの並びとして持ち、
+//! 各トークンにはソース上のバイト位置を付ける。トークンの文字列を順につなげると元のソースに戻る。
+//!
+//! ```
+//! use parser::cst::SyntaxTree;
+//!
+//! let source = "(LOOP)\n  @i   // i\r\n  0;JMP";
+//! assert_eq!(SyntaxTree::parse(source).to_string(), source);
+//! ```
+
+use crate::{
+    classify, expression::CHARACTER_QUOTE, InstructionType, A_INSTRUCTION_TOKEN, COMMENT_OUT_TOKEN,
+    C_INSTRUCTION_TOKEN_EQUAL, C_INSTRUCTION_TOKEN_SEMICOLON, ESCAPE, LIST_SEPARATOR,
+    L_INSTRUCTION_TOKEN_END, L_INSTRUCTION_TOKEN_START, STRING_QUOTE,
+};
+use std::fmt;
+
+const NEWLINE: char = '\n';
+const CARRIAGE_RETURN_NEWLINE: &str = "\r\n";
+// 2文字の演算子
+const SHIFT_OPERATORS: [&str; 2] = ["<<", ">>"];
+// 1文字の演算子
+const OPERATORS: [char; 9] = ['+', '-', '*', '/', '&', '|', '!', '<', '>'];
+
+/// トークンの種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    /// 改行以外の空白の並び
+    Whitespace,
+    /// `\n`または`\r\n`
+    Newline,
+    /// `//`から行末まで
+    Comment,
+    /// シンボル、ニーモニック、ディレクティブ、マクロの名前など
+    Name,
+    /// 数字で始まる語。`0x4000`や`1f`も含む
+    Number,
+    /// `"text"`。閉じていない場合は行末まで
+    String,
+    /// `'A'`。閉じていない場合は行末まで
+    Character,
+    /// `@`
+    At,
+    /// `(`
+    OpenParen,
+    /// `)`
+    CloseParen,
+    /// `=`
+    Equal,
+    /// `;`
+    Semicolon,
+    /// `,`
+    Comma,
+    /// `+`や`<<`などの演算子
+    Operator,
+}
+
+impl TokenKind {
+    /// 命令の意味に影響しない空白、改行、コメントか
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::Newline | TokenKind::Comment
+        )
+    }
+}
+
+/// ソース上のバイト位置の範囲。1から始まる文字の列で表す`parser::Span`とは単位が違う
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteSpan {
+    /// 0から始まるバイト位置
+    pub start: usize,
+    /// 範囲の直後のバイト位置
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: ByteSpan,
+}
+
+/// 行の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineKind {
+    /// 空行、またはコメントだけの行
+    Empty,
+    Instruction(InstructionType),
+    /// マクロの呼び出しなど、命令として解釈できない行
+    Other,
+}
+
+/// ソースの1行。行末の改行も含む
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxLine {
+    pub kind: LineKind,
+    /// 1から始まる行番号
+    pub line: usize,
+    pub tokens: Vec<Token>,
+}
+
+impl SyntaxLine {
+    /// 行全体の範囲
+    pub fn span(&self) -> ByteSpan {
+        match (self.tokens.first(), self.tokens.last()) {
+            (Some(first), Some(last)) => ByteSpan {
+                start: first.span.start,
+                end: last.span.end,
+            },
+            _ => ByteSpan { start: 0, end: 0 },
+        }
+    }
+
+    /// 前後の空白とコメントを除いた命令の部分。命令の中の空白はそのまま残す
+    pub fn code(&self) -> String {
+        let first = self.tokens.iter().position(|token| !token.kind.is_trivia());
+        let last = self
+            .tokens
+            .iter()
+            .rposition(|token| !token.kind.is_trivia());
+        match (first, last) {
+            (Some(first), Some(last)) => self.tokens[first..=last]
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect(),
+            _ => String::new(),
+        }
+    }
+
+    /// 行のコメント
+    pub fn comment(&self) -> Option<&Token> {
+        self.tokens
+            .iter()
+            .find(|token| token.kind == TokenKind::Comment)
+    }
+}
+
+impl fmt::Display for SyntaxLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tokens
+            .iter()
+            .try_for_each(|token| write!(f, "{}", token.text))
+    }
+}
+
+/// ソース全体の構文木
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree {
+    pub lines: Vec<SyntaxLine>,
+}
+
+impl SyntaxTree {
+    /// ソースを構文木にする。解釈できない命令も行として残すので失敗しない
+    pub fn parse(source: &str) -> Self {
+        let mut offset = 0;
+        let lines = source
+            .split_inclusive(NEWLINE)
+            .enumerate()
+            .map(|(i, text)| {
+                let line = parse_line(text, offset, i + 1);
+                offset += text.len();
+                line
+            })
+            .collect();
+        Self { lines }
+    }
+
+    /// すべてのトークンをソースの順に返す
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.lines.iter().flat_map(|line| line.tokens.iter())
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines.iter().try_for_each(|line| write!(f, "{}", line))
+    }
+}
+
+// 改行を含む1行をトークンに分ける。offsetは行の先頭のソース上の位置
+fn parse_line(text: &str, offset: usize, line: usize) -> SyntaxLine {
+    let content = text
+        .strip_suffix(CARRIAGE_RETURN_NEWLINE)
+        .or_else(|| text.strip_suffix(NEWLINE))
+        .unwrap_or(text);
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while start < content.len() {
+        let (kind, len) = next_token(&content[start..]);
+        tokens.push(Token {
+            kind,
+            text: content[start..start + len].to_string(),
+            span: ByteSpan {
+                start: offset + start,
+                end: offset + start + len,
+            },
+        });
+        start += len;
+    }
+    if content.len() < text.len() {
+        tokens.push(Token {
+            kind: TokenKind::Newline,
+            text: text[content.len()..].to_string(),
+            span: ByteSpan {
+                start: offset + content.len(),
+                end: offset + text.len(),
+            },
+        });
+    }
+
+    let mut syntax_line = SyntaxLine {
+        kind: LineKind::Empty,
+        line,
+        tokens,
+    };
+    let code = syntax_line.code();
+    if !code.is_empty() {
+        syntax_line.kind = classify(&code).map_or(LineKind::Other, LineKind::Instruction);
+    }
+    syntax_line
+}
+
+// 改行を含まない文字列の先頭のトークンの種類とバイト長
+pub(crate) fn next_token(text: &str) -> (TokenKind, usize) {
+    if text.starts_with(COMMENT_OUT_TOKEN) {
+        return (TokenKind::Comment, text.len());
+    }
+    let c = text.chars().next().unwrap();
+    if let Some(kind) = punctuation(c) {
+        return (kind, 1);
+    }
+    match c {
+        c if c.is_whitespace() => (
+            TokenKind::Whitespace,
+            text.find(|c: char| !c.is_whitespace())
+                .unwrap_or(text.len()),
+        ),
+        STRING_QUOTE => (TokenKind::String, quoted_len(text, STRING_QUOTE)),
+        CHARACTER_QUOTE => (TokenKind::Character, quoted_len(text, CHARACTER_QUOTE)),
+        _ if SHIFT_OPERATORS
+            .iter()
+            .any(|operator| text.starts_with(operator)) =>
+        {
+            (TokenKind::Operator, 2)
+        }
+        c if OPERATORS.contains(&c) => (TokenKind::Operator, 1),
+        c => {
+            let len = text
+                .char_indices()
+                .find(|(i, c)| i > &0 && !is_word_char(*c))
+                .map_or(text.len(), |(i, _)| i);
+            let kind = if c.is_ascii_digit() {
+                TokenKind::Number
+            } else {
+                TokenKind::Name
+            };
+            (kind, len)
+        }
+    }
+}
+
+// 演算子以外の1文字の記号
+fn punctuation(c: char) -> Option<TokenKind> {
+    match c {
+        A_INSTRUCTION_TOKEN => Some(TokenKind::At),
+        L_INSTRUCTION_TOKEN_START => Some(TokenKind::OpenParen),
+        L_INSTRUCTION_TOKEN_END => Some(TokenKind::CloseParen),
+        C_INSTRUCTION_TOKEN_EQUAL => Some(TokenKind::Equal),
+        C_INSTRUCTION_TOKEN_SEMICOLON => Some(TokenKind::Semicolon),
+        LIST_SEPARATOR => Some(TokenKind::Comma),
+        _ => None,
+    }
+}
+
+// 名前や数値の続きになる文字
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace()
+        && !OPERATORS.contains(&c)
+        && punctuation(c).is_none()
+        && c != STRING_QUOTE
+        && c != CHARACTER_QUOTE
+}
+
+// 引用符で始まる文字列の、閉じる引用符までのバイト長。閉じていなければ全体の長さ
+fn quoted_len(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            ESCAPE => escaped = true,
+            c if c == quote => return i + c.len_utf8(),
+            _ => (),
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(line: &SyntaxLine) -> Vec<TokenKind> {
+        line.tokens.iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sources = [
+            "",
+            "\n\n",
+            "(LOOP)\n  @i   // i\r\n\tMD=M+1//next\n\n// 日本語のコメント\n  0;JMP",
+            ".string msg \"a // \\\"b\" // text\n.data t 'x', '\\'', 1<<2\n",
+            ".string broken \"unterminated\n@'\n  INC  %x , y \r\n\r\n",
+        ];
+        for source in sources {
+            let tree = SyntaxTree::parse(source);
+            assert_eq!(tree.to_string(), source);
+            for token in tree.tokens() {
+                assert_eq!(&source[token.span.start..token.span.end], token.text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let tree = SyntaxTree::parse("(LOOP)\n  @i // i\r\nD=D-A;JNE\n\n// only\n  INC x\n.var n");
+        let lines: Vec<LineKind> = tree.lines.iter().map(|line| line.kind).collect();
+        assert_eq!(
+            lines,
+            vec![
+                LineKind::Instruction(InstructionType::L),
+                LineKind::Instruction(InstructionType::A),
+                LineKind::Instruction(InstructionType::C),
+                LineKind::Empty,
+                LineKind::Empty,
+                LineKind::Other,
+                LineKind::Instruction(InstructionType::Directive),
+            ]
+        );
+
+        let line = &tree.lines[1];
+        assert_eq!(line.line, 2);
+        assert_eq!(
+            kinds(line),
+            vec![
+                TokenKind::Whitespace,
+                TokenKind::At,
+                TokenKind::Name,
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+                TokenKind::Newline,
+            ]
+        );
+        assert_eq!(line.code(), "@i");
+        assert_eq!(line.comment().unwrap().text, "// i");
+        assert_eq!(line.span(), ByteSpan { start: 7, end: 18 });
+        assert_eq!(line.tokens[5].text, "\r\n");
+
+        assert_eq!(
+            kinds(&tree.lines[2]),
+            vec![
+                TokenKind::Name,
+                TokenKind::Equal,
+                TokenKind::Name,
+                TokenKind::Operator,
+                TokenKind::Name,
+                TokenKind::Semicolon,
+                TokenKind::Name,
+                TokenKind::Newline,
+            ]
+        );
+
+        let tree = SyntaxTree::parse("@0x10 << 1f\n.string s \"a//b\"");
+        let texts: Vec<(TokenKind, &str)> = tree
+            .tokens()
+            .filter(|token| !token.kind.is_trivia())
+            .map(|token| (token.kind, token.text.as_str()))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (TokenKind::At, "@"),
+                (TokenKind::Number, "0x10"),
+                (TokenKind::Operator, "<<"),
+                (TokenKind::Number, "1f"),
+                (TokenKind::Name, ".string"),
+                (TokenKind::Name, "s"),
+                (TokenKind::String, "\"a//b\""),
+            ]
+        );
+    }
+}
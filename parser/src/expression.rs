@@ -0,0 +1,436 @@
+//! A命令のオペランドやディレクティブの値に書ける式
+//!
+//! 数値・シンボル・括弧と次の演算子を組み合わせられる。優先順位はC言語と同じ。
+//! 数値は10進数のほか`0x4000`(16進数)、`0b1010`(2進数)、`'A'`(ASCIIコード)と書ける。
+//! `$`はその命令自身のROMアドレス、`1f`/`1b`は前方/後方で最も近い数値ローカルラベル`1:`を表す。
+//!
+//! | 優先順位 | 演算子 |
+//! |---|---|
+//! | 高 | 単項`-` |
+//! | | `*` `/` |
+//! | | `+` `-` |
+//! | | `<<` `>>` |
+//! | | `&` |
+//! | 低 | `\|` |
+
+use anyhow::{anyhow, bail, Result};
+use error_code::ErrorCode;
+use message::tr;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(i64),
+    Symbol(String),
+    /// `$`
+    CurrentAddress,
+    /// `1f`/`1b`
+    LocalLabel(u32, Direction),
+    Negate(Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+/// 数値ローカルラベルを参照する方向
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    And,
+    Or,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::And => "&",
+            BinaryOperator::Or => "|",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+// 優先順位の低い順に並べた二項演算子
+const PRECEDENCE: [&[BinaryOperator]; 5] = [
+    &[BinaryOperator::Or],
+    &[BinaryOperator::And],
+    &[BinaryOperator::ShiftLeft, BinaryOperator::ShiftRight],
+    &[BinaryOperator::Add, BinaryOperator::Subtract],
+    &[BinaryOperator::Multiply, BinaryOperator::Divide],
+];
+
+pub(crate) const CHARACTER_QUOTE: char = '\'';
+const CURRENT_ADDRESS_TOKEN: &str = "$";
+const ESCAPE: char = '\\';
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Symbol(String),
+    CurrentAddress,
+    LocalLabel(u32, Direction),
+    Operator(BinaryOperator),
+    OpenParen,
+    CloseParen,
+}
+
+impl Expression {
+    // シンボルなど数値以外の項の値を問い合わせながら式を計算する
+    pub fn evaluate(&self, lookup: &dyn Fn(&Expression) -> Option<i64>) -> Result<i64> {
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Symbol(symbol) => lookup(self).ok_or_else(|| {
+                anyhow!(ErrorCode::UndefinedSymbol.error(tr!(undefined_symbol, symbol)))
+            }),
+            Expression::CurrentAddress => lookup(self).ok_or_else(|| {
+                anyhow!(ErrorCode::CurrentAddressNotAllowed.error(tr!(current_address_not_allowed)))
+            }),
+            Expression::LocalLabel(number, direction) => lookup(self).ok_or_else(|| {
+                let message = match direction {
+                    Direction::Forward => tr!(local_label_not_found_after, number),
+                    Direction::Backward => tr!(local_label_not_found_before, number),
+                };
+                anyhow!(ErrorCode::LocalLabelNotFound.error(message))
+            }),
+            Expression::Negate(operand) => {
+                let value = operand.evaluate(lookup)?;
+                // -xは0-xとして報告する
+                value.checked_neg().ok_or_else(|| {
+                    anyhow!(ErrorCode::ArithmeticError.error(tr!(
+                        overflow,
+                        0,
+                        BinaryOperator::Subtract,
+                        value
+                    )))
+                })
+            }
+            Expression::Binary(operator, left, right) => {
+                let (left, right) = (left.evaluate(lookup)?, right.evaluate(lookup)?);
+                let value = match operator {
+                    BinaryOperator::Add => left.checked_add(right),
+                    BinaryOperator::Subtract => left.checked_sub(right),
+                    BinaryOperator::Multiply => left.checked_mul(right),
+                    BinaryOperator::Divide if right == 0 => {
+                        bail!(ErrorCode::ArithmeticError.error(tr!(division_by_zero)))
+                    }
+                    BinaryOperator::Divide => left.checked_div(right),
+                    BinaryOperator::And => Some(left & right),
+                    BinaryOperator::Or => Some(left | right),
+                    BinaryOperator::ShiftLeft => u32::try_from(right)
+                        .ok()
+                        .and_then(|right| left.checked_shl(right)),
+                    BinaryOperator::ShiftRight => u32::try_from(right)
+                        .ok()
+                        .and_then(|right| left.checked_shr(right)),
+                };
+                value.ok_or_else(|| {
+                    anyhow!(ErrorCode::ArithmeticError.error(tr!(overflow, left, operator, right)))
+                })
+            }
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Expression> {
+    let tokens = tokenize(text)?;
+    let mut position = 0;
+    let expression = parse_binary(&tokens, &mut position, 0)?;
+    match tokens.get(position) {
+        None => Ok(expression),
+        Some(_) => bail!(ErrorCode::InvalidExpression.error(tr!(unexpected_token, text))),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            c if c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(&c) = chars
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+                {
+                    number.push(c);
+                    chars.next();
+                }
+                match local_label(&number) {
+                    Some((label, direction)) => Token::LocalLabel(label, direction),
+                    None => Token::Number(parse_number(&number)?),
+                }
+            }
+            CHARACTER_QUOTE => {
+                chars.next();
+                let c = match chars.next() {
+                    Some(ESCAPE) => chars.next(),
+                    c => c,
+                };
+                match (c, chars.next()) {
+                    (Some(c), Some(CHARACTER_QUOTE)) if c.is_ascii() => Token::Number(c as i64),
+                    (Some(c), Some(CHARACTER_QUOTE)) => {
+                        bail!(ErrorCode::InvalidExpression.error(tr!(character_not_ascii, c)))
+                    }
+                    _ => {
+                        let message = tr!(invalid_character_literal, text);
+                        bail!(ErrorCode::InvalidExpression.error(message))
+                    }
+                }
+            }
+            c if is_symbol_start(c) => {
+                let mut symbol = String::new();
+                while let Some(&c) = chars.peek().filter(|c| is_symbol_char(**c)) {
+                    symbol.push(c);
+                    chars.next();
+                }
+                match symbol.as_str() {
+                    CURRENT_ADDRESS_TOKEN => Token::CurrentAddress,
+                    _ => Token::Symbol(symbol),
+                }
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '+' => Token::Operator(BinaryOperator::Add),
+                    '-' => Token::Operator(BinaryOperator::Subtract),
+                    '*' => Token::Operator(BinaryOperator::Multiply),
+                    '/' => Token::Operator(BinaryOperator::Divide),
+                    '&' => Token::Operator(BinaryOperator::And),
+                    '|' => Token::Operator(BinaryOperator::Or),
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    '<' | '>' if chars.next_if_eq(&c).is_some() => Token::Operator(match c {
+                        '<' => BinaryOperator::ShiftLeft,
+                        _ => BinaryOperator::ShiftRight,
+                    }),
+                    _ => bail!(ErrorCode::InvalidExpression.error(tr!(
+                        unexpected_character,
+                        c,
+                        text
+                    ))),
+                }
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// 数字の後にfかbが続く数値ローカルラベルの参照
+fn local_label(v: &str) -> Option<(u32, Direction)> {
+    let direction = match v.chars().last()? {
+        'f' => Direction::Forward,
+        'b' => Direction::Backward,
+        _ => return None,
+    };
+    v[..v.len() - 1]
+        .parse()
+        .ok()
+        .map(|label| (label, direction))
+}
+
+// 10進数、0xで始まる16進数、0bで始まる2進数。桁区切りの_は無視する
+fn parse_number(number: &str) -> Result<i64> {
+    let digits = number.replace('_', "");
+    let parsed = match digits.get(..2) {
+        Some("0x") | Some("0X") => i64::from_str_radix(&digits[2..], 16),
+        Some("0b") | Some("0B") => i64::from_str_radix(&digits[2..], 2),
+        _ => digits.parse(),
+    };
+    parsed.map_err(|_| anyhow!(ErrorCode::InvalidNumber.error(tr!(invalid_number, number))))
+}
+
+fn is_symbol_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || ['_', '.', '$', ':'].contains(&c)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ['_', '.', '$', ':'].contains(&c)
+}
+
+// level: PRECEDENCEの何番目の演算子を扱うか
+fn parse_binary(tokens: &[Token], position: &mut usize, level: usize) -> Result<Expression> {
+    if level == PRECEDENCE.len() {
+        return parse_unary(tokens, position);
+    }
+    let mut left = parse_binary(tokens, position, level + 1)?;
+    while let Some(Token::Operator(operator)) = tokens.get(*position) {
+        if !PRECEDENCE[level].contains(operator) {
+            break;
+        }
+        *position += 1;
+        let right = parse_binary(tokens, position, level + 1)?;
+        left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+    }
+
+    Ok(left)
+}
+
+fn parse_unary(tokens: &[Token], position: &mut usize) -> Result<Expression> {
+    let token = tokens.get(*position).ok_or_else(|| {
+        anyhow!(ErrorCode::InvalidExpression.error(tr!(unexpected_end_of_expression)))
+    })?;
+    *position += 1;
+    match token {
+        Token::Number(value) => Ok(Expression::Number(*value)),
+        Token::Symbol(symbol) => Ok(Expression::Symbol(symbol.clone())),
+        Token::CurrentAddress => Ok(Expression::CurrentAddress),
+        Token::LocalLabel(label, direction) => Ok(Expression::LocalLabel(*label, *direction)),
+        Token::Operator(BinaryOperator::Subtract) => {
+            Ok(Expression::Negate(Box::new(parse_unary(tokens, position)?)))
+        }
+        Token::OpenParen => {
+            let expression = parse_binary(tokens, position, 0)?;
+            match tokens.get(*position) {
+                Some(Token::CloseParen) => {
+                    *position += 1;
+                    Ok(expression)
+                }
+                _ => bail!(ErrorCode::InvalidExpression.error(tr!(missing_close_paren))),
+            }
+        }
+        Token::Operator(operator) => {
+            bail!(ErrorCode::InvalidExpression.error(tr!(unexpected_operator, operator)))
+        }
+        Token::CloseParen => {
+            bail!(ErrorCode::InvalidExpression.error(tr!(unexpected_close_paren)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(text: &str) -> Result<i64> {
+        parse(text)?.evaluate(&|term| match term {
+            Expression::Symbol(symbol) if symbol == "SCREEN" => Some(16384),
+            Expression::Symbol(symbol) if symbol == "LOOP" => Some(10),
+            Expression::CurrentAddress => Some(100),
+            Expression::LocalLabel(1, Direction::Backward) => Some(90),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(parse("42")?, Expression::Number(42));
+        assert_eq!(
+            parse("ball.x$1")?,
+            Expression::Symbol("ball.x$1".to_string())
+        );
+        assert_eq!(
+            parse("LOOP-1")?,
+            Expression::Binary(
+                BinaryOperator::Subtract,
+                Box::new(Expression::Symbol("LOOP".to_string())),
+                Box::new(Expression::Number(1))
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate() -> Result<()> {
+        assert_eq!(evaluate("SCREEN+32")?, 16416);
+        assert_eq!(evaluate("LOOP-1")?, 9);
+        assert_eq!(evaluate("1+2*3")?, 7);
+        assert_eq!(evaluate("(1+2)*3")?, 9);
+        assert_eq!(evaluate("10-4-3")?, 3);
+        assert_eq!(evaluate("100/7")?, 14);
+        assert_eq!(evaluate("1<<4|1")?, 17);
+        assert_eq!(evaluate("255 & 15 << 2")?, 60);
+        assert_eq!(evaluate("SCREEN >> 8")?, 64);
+        assert_eq!(evaluate("-LOOP+20")?, 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_literals() -> Result<()> {
+        assert_eq!(evaluate("0x4000")?, 16384);
+        assert_eq!(evaluate("0X7fff")?, 32767);
+        assert_eq!(evaluate("0b1010")?, 10);
+        assert_eq!(evaluate("0b0000_1111")?, 15);
+        assert_eq!(evaluate("1_000")?, 1000);
+        assert_eq!(evaluate("'A'")?, 65);
+        assert_eq!(evaluate("' '")?, 32);
+        assert_eq!(evaluate("'\\''")?, 39);
+        assert_eq!(evaluate("'\\\\'")?, 92);
+        assert_eq!(evaluate("'a'-'A'+SCREEN")?, 16416);
+        Ok(())
+    }
+
+    #[test]
+    fn test_current_address_and_local_label() -> Result<()> {
+        assert_eq!(parse("$")?, Expression::CurrentAddress);
+        assert_eq!(parse("$end")?, Expression::Symbol("$end".to_string()));
+        assert_eq!(
+            parse("12f")?,
+            Expression::LocalLabel(12, Direction::Forward)
+        );
+        assert_eq!(parse("0b")?, Expression::LocalLabel(0, Direction::Backward));
+        assert_eq!(parse("0b1")?, Expression::Number(1));
+        assert_eq!(evaluate("$+3")?, 103);
+        assert_eq!(evaluate("1b")?, 90);
+        assert_eq!(
+            evaluate("1f").unwrap_err().to_string(),
+            "local label 1: not found after this instruction"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| evaluate(text).unwrap_err().to_string();
+        assert_eq!(error("i+1"), "undefined symbol i in expression");
+        assert_eq!(error("1/0"), "division by zero");
+        assert_eq!(error("(1+2"), "missing ')' in expression");
+        assert_eq!(error("1+"), "unexpected end of expression");
+        assert_eq!(error("1 2"), "unexpected token in expression \"1 2\"");
+        assert_eq!(
+            error("1<2"),
+            "unexpected character '<' in expression \"1<2\""
+        );
+        assert_eq!(error("12ab"), "invalid number \"12ab\"");
+        assert_eq!(error("*2"), "unexpected operator * in expression");
+        assert_eq!(error("0x"), "invalid number \"0x\"");
+        assert_eq!(error("0b102"), "invalid number \"0b102\"");
+        assert_eq!(
+            error("0xFFFFFFFFFFFFFFFFFF"),
+            "invalid number \"0xFFFFFFFFFFFFFFFFFF\""
+        );
+        assert_eq!(
+            error("'AB'"),
+            "invalid character literal in expression \"'AB'\""
+        );
+        assert_eq!(
+            error("'A"),
+            "invalid character literal in expression \"'A\""
+        );
+        assert_eq!(error("'あ'"), "character literal must be ASCII: 'あ'");
+        assert_eq!(
+            error("-(-9223372036854775807-1)"),
+            "overflow in 0 - -9223372036854775808"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! `.include "file.asm"`ディレクティブによる他ファイルの取り込み
+//!
+//! 取り込むファイルは、取り込み元のファイルがあるディレクトリ、検索パスの順に探す。
+//! 取り込まれた行は元のファイル名と行番号を保持する。
+
+use crate::{source_lines, Location, SourceLine};
+use anyhow::{anyhow, bail, Context, Result};
+use error_code::ErrorCode;
+use message::tr;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const INCLUDE_TOKEN: &str = ".include";
+
+// ファイルを読み込み、`.include`を取り込んだ行を返す
+pub fn load(path: &Path, search_paths: &[PathBuf]) -> Result<Vec<SourceLine>> {
+    load_file(path, search_paths, &mut Vec::new())
+}
+
+// ファイル以外から読み込んだ行の`.include`を取り込む。相対パスはカレントディレクトリ、検索パスの順に探す
+pub fn expand(lines: Vec<SourceLine>, search_paths: &[PathBuf]) -> Result<Vec<SourceLine>> {
+    expand_lines(lines, None, search_paths, &mut Vec::new())
+}
+
+// includes: 取り込み中のファイルの並び(循環の検出に使う)
+fn load_file(
+    path: &Path,
+    search_paths: &[PathBuf],
+    includes: &mut Vec<PathBuf>,
+) -> Result<Vec<SourceLine>> {
+    let canonical_path =
+        fs::canonicalize(path).with_context(|| tr!(cannot_open, path.display()))?;
+    if let Some(start) = includes.iter().position(|p| *p == canonical_path) {
+        let cycle: Vec<String> = includes[start..]
+            .iter()
+            .chain([&canonical_path])
+            .map(|p| p.display().to_string())
+            .collect();
+        bail!(ErrorCode::IncludeCycle.error(tr!(include_cycle, cycle.join(" -> "))));
+    }
+    let source = fs::read_to_string(path).with_context(|| tr!(cannot_read, path.display()))?;
+    let file = path.display().to_string();
+    let lines = source_lines(&source)
+        .into_iter()
+        .map(|line| SourceLine {
+            location: Location {
+                file: Some(file.clone()),
+                ..line.location
+            },
+            ..line
+        })
+        .collect();
+
+    includes.push(canonical_path);
+    let lines = expand_lines(lines, path.parent(), search_paths, includes);
+    includes.pop();
+
+    lines
+}
+
+fn expand_lines(
+    lines: Vec<SourceLine>,
+    base_dir: Option<&Path>,
+    search_paths: &[PathBuf],
+    includes: &mut Vec<PathBuf>,
+) -> Result<Vec<SourceLine>> {
+    let mut expanded = Vec::new();
+    for line in lines {
+        match include_file(&line.text).with_context(|| line.location.clone())? {
+            Some(file) => {
+                let path =
+                    resolve(file, base_dir, search_paths).with_context(|| line.location.clone())?;
+                expanded.extend(
+                    load_file(&path, search_paths, includes)
+                        .with_context(|| line.location.clone())?,
+                );
+            }
+            None => expanded.push(line),
+        }
+    }
+
+    Ok(expanded)
+}
+
+// `.include "file"`の行であればファイル名を返す
+fn include_file(text: &str) -> Result<Option<&str>> {
+    let text = text.trim();
+    match text.strip_prefix(INCLUDE_TOKEN) {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => rest
+            .trim()
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .filter(|file| !file.is_empty())
+            .map(Some)
+            .ok_or_else(|| {
+                anyhow!(ErrorCode::MalformedInclude.error(tr!(
+                    malformed_include,
+                    INCLUDE_TOKEN,
+                    text
+                )))
+            }),
+        _ => Ok(None),
+    }
+}
+
+fn resolve(file: &str, base_dir: Option<&Path>, search_paths: &[PathBuf]) -> Result<PathBuf> {
+    let base_dir = base_dir.unwrap_or(Path::new(""));
+    [base_dir]
+        .into_iter()
+        .chain(search_paths.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(file))
+        .find(|path| path.is_file())
+        .ok_or_else(|| anyhow!(ErrorCode::IncludeNotFound.error(tr!(include_not_found, file))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::distr::{Alphanumeric, SampleString};
+
+    fn create_test_dir() -> PathBuf {
+        let dirname = Alphanumeric.sample_string(&mut rand::rng(), 5);
+        //bacon testでファイル変更検知が発生しないようにtargetディレクトリにテストファイルを作成する。
+        let dir = Path::new("../target/test/data").join(dirname);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn texts(lines: &[SourceLine]) -> Vec<&str> {
+        lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_load() -> Result<()> {
+        let dir = create_test_dir();
+        let lib_dir = dir.join("lib");
+        fs::create_dir_all(&lib_dir)?;
+        fs::write(
+            dir.join("Main.asm"),
+            "@1\n.include \"Sub.asm\"\n.include \"Lib.asm\"\nD=A",
+        )?;
+        fs::write(dir.join("Sub.asm"), "// sub\n@2")?;
+        fs::write(lib_dir.join("Lib.asm"), "@3")?;
+
+        let lines = load(&dir.join("Main.asm"), &[lib_dir])?;
+        assert_eq!(texts(&lines), vec!["@1", "// sub", "@2", "@3", "D=A"]);
+
+        //取り込まれた行は元のファイル名と行番号を持つ
+        let sub = dir.join("Sub.asm").display().to_string();
+        assert_eq!(lines[2].location.to_string(), format!("{}:2", sub));
+        assert_eq!(lines[4].location.line, 4);
+
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_errors() -> Result<()> {
+        let dir = create_test_dir();
+        fs::write(dir.join("A.asm"), ".include \"B.asm\"")?;
+        fs::write(dir.join("B.asm"), "@1\n.include \"A.asm\"")?;
+        fs::write(dir.join("C.asm"), "@1\n.include \"Missing.asm\"")?;
+        fs::write(dir.join("D.asm"), ".include Missing.asm")?;
+
+        let error = format!("{:#}", load(&dir.join("A.asm"), &[]).unwrap_err());
+        assert!(error.contains("include cycle detected"));
+        assert!(error.contains("A.asm -> "));
+
+        let error = format!("{:#}", load(&dir.join("C.asm"), &[]).unwrap_err());
+        assert!(error.ends_with("C.asm:2: include file \"Missing.asm\" not found"));
+
+        let error = format!("{:#}", load(&dir.join("D.asm"), &[]).unwrap_err());
+        assert!(error.ends_with("expected .include \"file\" but found \".include Missing.asm\""));
+
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_file() -> Result<()> {
+        assert_eq!(include_file("  .include \"a.asm\"")?, Some("a.asm"));
+        assert_eq!(include_file("@include")?, None);
+        assert_eq!(include_file(".included")?, None);
+        assert!(include_file(".include").is_err());
+        assert!(include_file(".include \"\"").is_err());
+        Ok(())
+    }
+}
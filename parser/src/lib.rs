@@ -1,8 +1,13 @@
-use anyhow::Result;
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
+use anyhow::{anyhow, bail, Context, Result};
+use error_code::ErrorCode;
+use expression::Expression;
+use message::tr;
+use std::{collections::VecDeque, fmt, fs};
+
+pub mod cst;
+pub mod expression;
+pub mod include;
+pub mod macros;
 
 const COMMENT_OUT_TOKEN: &str = "//";
 const A_INSTRUCTION_TOKEN: char = '@';
@@ -10,38 +15,336 @@ const C_INSTRUCTION_TOKEN_EQUAL: char = '=';
 const C_INSTRUCTION_TOKEN_SEMICOLON: char = ';';
 const L_INSTRUCTION_TOKEN_START: char = '(';
 const L_INSTRUCTION_TOKEN_END: char = ')';
-
-#[derive(Debug, PartialEq)]
+const NUMERIC_LABEL_TOKEN_END: char = ':';
+const DIRECTIVE_TOKEN: char = '.';
+const EQU_DIRECTIVE_TOKENS: [&str; 2] = [".equ", ".define"];
+const VAR_DIRECTIVE_TOKEN: &str = ".var";
+const ORG_DIRECTIVE_TOKEN: &str = ".org";
+const RECORD_DIRECTIVE_TOKEN: &str = ".record";
+const DATA_DIRECTIVE_TOKEN: &str = ".data";
+const STRING_DIRECTIVE_TOKEN: &str = ".string";
+const LIST_SEPARATOR: char = ',';
+const STRING_QUOTE: char = '"';
+const ESCAPE: char = '\\';
+// シンボル名に使えない、C命令のレジスタの名前
+const REGISTER_NAMES: [&str; 3] = ["A", "D", "M"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InstructionType {
     A,
     C,
     L,
+    /// `.`で始まるアセンブラへの指示
+    Directive,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Directive {
+    /// `.equ NAME value`または`.define NAME value`
+    Equ { name: String, value: String },
+    /// `.var name [size]`
+    Var { name: String, size: Option<String> },
+    /// `.org address`: 以降の変数をaddressから割り当てる
+    Org { address: String },
+    /// `.record NAME field [size], ...`
+    Record {
+        name: String,
+        fields: Vec<(String, Option<String>)>,
+    },
+    /// `.data name value, ...`: 初期値を持つRAMの領域
+    Data { name: String, values: Vec<String> },
+    /// `.string name "text"`: 0で終わる文字列を格納するRAMの領域
+    String { name: String, text: String },
+}
+
+/// エラー発生箇所を示すソース上の位置
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    /// ファイルから読み込んだ行の場合はそのファイルのパス
+    pub file: Option<String>,
+    pub line: usize,
+    /// 行の中で位置の指す命令やトークンの範囲。行全体を指す場合とマクロ展開で生成された行はNone
+    pub span: Option<Span>,
+    /// マクロ展開で生成された行の場合はその展開元
+    pub expansion: Option<Box<Expansion>>,
+}
+
+/// 行の中の範囲。列は1から文字単位で数え、endは範囲の直後の列。ソース上のバイト位置は`cst::ByteSpan`で表す
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// マクロの呼び出し元
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+    pub name: String,
+    pub call_site: Location,
+}
+
+impl Location {
+    pub fn new(line: usize) -> Self {
+        Self {
+            file: None,
+            line,
+            span: None,
+            expansion: None,
+        }
+    }
+
+    /// 範囲を除いて同じ行を指しているか
+    pub fn is_same_line(&self, other: &Location) -> bool {
+        self.file == other.file && self.line == other.line && self.expansion == other.expansion
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}", file, self.line)?,
+            None => write!(f, "{}", tr!(line, self.line))?,
+        }
+        if let Some(expansion) = &self.expansion {
+            write!(
+                f,
+                "{}",
+                tr!(in_expansion, expansion.name, expansion.call_site)
+            )?;
+        }
+        Ok(())
+    }
 }
+
+/// 位置情報付きのソースの1行
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub text: String,
+    pub location: Location,
+}
+
+// ソース文字列を行番号付きの行に分割する。命令の後ろのコメントは取り除き、
+// コメントだけの行はプラグマを読むために残す
+pub fn source_lines(source: &str) -> Vec<SourceLine> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let text = match split_comment(line) {
+                (code, Some(_)) if !code.trim().is_empty() => code.trim_end(),
+                _ => line,
+            };
+            SourceLine {
+                text: text.to_string(),
+                location: Location::new(i + 1),
+            }
+        })
+        .collect()
+}
+
+/// 行を命令の部分と`//`から始まるコメントに分ける。文字列や文字リテラルの中の`//`はコメントではない
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    // トークンの区切りは構文木と同じ字句解析で決める
+    let mut start = 0;
+    while start < line.len() {
+        let (kind, len) = cst::next_token(&line[start..]);
+        if kind == cst::TokenKind::Comment {
+            return (&line[..start], Some(&line[start..]));
+        }
+        start += len;
+    }
+    (line, None)
+}
+
+/// 数字以外で始まり、英数字と_.$:からなる名前か。レジスタの名前(A、D、M)はシンボル名に使えない
+pub fn is_symbol_name(v: &str) -> bool {
+    v.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && v.chars().all(is_symbol_char)
+        && !REGISTER_NAMES.contains(&v)
+}
+
+// シンボルに使える文字か
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ['_', '.', '$', ':'].contains(&c)
+}
+
+// 命令の中でsymbolがトークンとして最初に現れるバイト位置
+fn find_symbol(instruction: &str, symbol: &str) -> Option<usize> {
+    instruction
+        .match_indices(symbol)
+        .map(|(i, _)| i)
+        .find(|&i| {
+            !instruction[..i].ends_with(is_symbol_char)
+                && !instruction[i + symbol.len()..].starts_with(is_symbol_char)
+        })
+}
+
+// `1:`のような数値ローカルラベルの定義であれば数値部分を返す
+fn numeric_label(instruction: &str) -> Option<&str> {
+    instruction
+        .strip_suffix(NUMERIC_LABEL_TOKEN_END)
+        .filter(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_digit()))
+}
+
+// コメントと前後の空白を除いた命令の種類。マクロの呼び出しなど命令でなければNone
+fn classify(instruction: &str) -> Option<InstructionType> {
+    match instruction {
+        instruction if instruction.starts_with(A_INSTRUCTION_TOKEN) => Some(InstructionType::A),
+        instruction if instruction.starts_with(DIRECTIVE_TOKEN) => Some(InstructionType::Directive),
+        instruction
+            if instruction.starts_with(L_INSTRUCTION_TOKEN_START)
+                && instruction.ends_with(L_INSTRUCTION_TOKEN_END) =>
+        {
+            Some(InstructionType::L)
+        }
+        instruction if numeric_label(instruction).is_some() => Some(InstructionType::L),
+        instruction
+            if instruction.contains(C_INSTRUCTION_TOKEN_SEMICOLON)
+                || instruction.contains(C_INSTRUCTION_TOKEN_EQUAL) =>
+        {
+            Some(InstructionType::C)
+        }
+        _ => None,
+    }
+}
+
+// `"text"`の引用符を外し、`\`によるエスケープを解く
+fn string_literal(v: &str) -> Result<String> {
+    let inner = v
+        .strip_prefix(STRING_QUOTE)
+        .and_then(|v| v.strip_suffix(STRING_QUOTE))
+        .ok_or_else(|| anyhow!(ErrorCode::InvalidString.error(tr!(expected_quoted_string, v))))?;
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            ESCAPE => chars.next().ok_or_else(|| {
+                anyhow!(ErrorCode::InvalidString.error(tr!(unterminated_escape, v)))
+            })?,
+            STRING_QUOTE => {
+                bail!(ErrorCode::InvalidString.error(tr!(unescaped_quote, v)))
+            }
+            c => c,
+        };
+        if !c.is_ascii() {
+            bail!(ErrorCode::InvalidString.error(tr!(string_not_ascii, v)));
+        }
+        text.push(c);
+    }
+
+    Ok(text)
+}
+
+#[derive(Clone)]
 pub struct Parser {
-    assembly: Box<dyn BufRead>,
+    assembly: VecDeque<SourceLine>,
     current_instruction: Option<String>,
+    current_location: Location,
+    // 現在の命令の前にある空白の文字数
+    current_indent: usize,
 }
 
 impl Parser {
-    pub fn new(filename: &str) -> Self {
+    pub fn new(filename: &str) -> Result<Self> {
+        let source = fs::read_to_string(filename).with_context(|| tr!(cannot_read, filename))?;
+        Ok(Self::from_source(&source))
+    }
+
+    // ファイルを介さずにアセンブリのソース文字列を読み込む
+    pub fn from_source(source: &str) -> Self {
+        Self::from_lines(source_lines(source))
+    }
+
+    // マクロ展開などの前処理を済ませた行を読み込む
+    pub fn from_lines(lines: Vec<SourceLine>) -> Self {
         Self {
-            assembly: Box::new(BufReader::new(File::open(filename).unwrap())),
+            assembly: lines.into(),
             current_instruction: None,
+            current_location: Location::default(),
+            current_indent: 0,
+        }
+    }
+
+    // 現在の命令のソース上の位置。範囲は命令全体
+    pub fn location(&self) -> Location {
+        let length = self.current_instruction.as_ref().map_or(0, String::len);
+        self.location_of(0, length)
+    }
+
+    /// 現在の命令の中でシンボルが書かれている位置。`PARENT.loop`のように修飾された名前は
+    /// 命令に書かれた`.loop`を探し、見つからなければ命令全体を指す
+    pub fn symbol_location(&self, symbol: &str) -> Location {
+        let instruction = self.current_instruction.as_deref().unwrap_or("");
+        let found = find_symbol(instruction, symbol)
+            .map(|i| (i, symbol.len()))
+            .or_else(|| {
+                symbol.match_indices('.').find_map(|(i, _)| {
+                    find_symbol(instruction, &symbol[i..]).map(|j| (j, symbol.len() - i))
+                })
+            });
+        let (start, length) = found.unwrap_or((0, instruction.len()));
+        self.location_of(start, length)
+    }
+
+    /// C命令のdestが書かれている位置
+    pub fn dest_location(&self) -> Location {
+        self.part_location(0, self.dest().unwrap_or_default())
+    }
+
+    /// C命令のcompが書かれている位置
+    pub fn comp_location(&self) -> Location {
+        let start = self
+            .current_instruction
+            .as_deref()
+            .and_then(|instruction| instruction.find(C_INSTRUCTION_TOKEN_EQUAL))
+            .map_or(0, |i| i + 1);
+        self.part_location(start, self.comp().unwrap_or_default())
+    }
+
+    /// C命令のjumpが書かれている位置
+    pub fn jump_location(&self) -> Location {
+        let start = self
+            .current_instruction
+            .as_deref()
+            .and_then(|instruction| instruction.find(C_INSTRUCTION_TOKEN_SEMICOLON))
+            .map_or(0, |i| i + 1);
+        self.part_location(start, self.jump().unwrap_or_default())
+    }
+
+    // 命令のstartバイト目から始まるpartの、前後の空白を除いた部分を指す位置
+    fn part_location(&self, start: usize, part: &str) -> Location {
+        let start = start + part.len() - part.trim_start().len();
+        self.location_of(start, part.trim().len())
+    }
+
+    // 現在の命令のstartバイト目からlengthバイトを指す位置。マクロ展開で生成された行は
+    // 引数の置き換えでマクロ本体の行と列がずれるので、範囲を付けない
+    fn location_of(&self, start: usize, length: usize) -> Location {
+        let instruction = self.current_instruction.as_deref().unwrap_or("");
+        let column = |i: usize| self.current_indent + instruction[..i].chars().count() + 1;
+        let span = (length > 0 && self.current_location.expansion.is_none()).then(|| Span {
+            start: column(start),
+            end: column(start + length),
+        });
+        Location {
+            span,
+            ..self.current_location.clone()
         }
     }
 
     pub fn has_more_lines(&mut self) -> Result<bool> {
-        Ok(self.assembly.fill_buf()?.iter().next().is_some())
+        Ok(!self.assembly.is_empty())
     }
 
     pub fn advance(&mut self) -> Result<()> {
         // //で始まるコメント行と空白を無視して次の行を読み込む
-        while self.has_more_lines()? {
-            self.current_instruction = match self.assembly.as_mut().lines().next().unwrap() {
-                Ok(line) if line.chars().all(char::is_whitespace) => None, //空白の場合は無視
-                Ok(line) if line.trim().starts_with(COMMENT_OUT_TOKEN) => None,   //コメント行の場合は無視
-                Ok(line) => Some(line.trim().to_string()),
-                Err(_) => None,
+        while let Some(line) = self.assembly.pop_front() {
+            self.current_location = line.location;
+            self.current_indent = line.text.chars().take_while(|c| c.is_whitespace()).count();
+            self.current_instruction = match line.text {
+                line if line.chars().all(char::is_whitespace) => None, //空白の場合は無視
+                line if line.trim().starts_with(COMMENT_OUT_TOKEN) => None, //コメント行の場合は無視
+                line => Some(line.trim().to_string()),
             };
             if self.current_instruction.is_some() {
                 break;
@@ -52,25 +355,12 @@ impl Parser {
 
     pub fn instruction_type(&self) -> Result<Option<InstructionType>> {
         match &self.current_instruction {
-            Some(instruction) if instruction.starts_with(A_INSTRUCTION_TOKEN) => {
-                Ok(Some(InstructionType::A))
-            }
-            Some(instruction)
-                if instruction.starts_with(L_INSTRUCTION_TOKEN_START)
-                    && instruction.ends_with(L_INSTRUCTION_TOKEN_END) =>
-            {
-                Ok(Some(InstructionType::L))
-            }
-            Some(instruction)
-                if instruction.contains(C_INSTRUCTION_TOKEN_SEMICOLON)
-                    || instruction.contains(C_INSTRUCTION_TOKEN_EQUAL) =>
-            {
-                Ok(Some(InstructionType::C))
-            }
-            Some(instruction) => panic!(
-                "parse instruction type error. instruction_value: {:?}",
-                instruction
-            ),
+            Some(instruction) => match classify(instruction) {
+                Some(instruction_type) => Ok(Some(instruction_type)),
+                None => {
+                    bail!(ErrorCode::InvalidInstruction.error(tr!(invalid_instruction, instruction)))
+                }
+            },
             None => Ok(None),
         }
     }
@@ -85,14 +375,137 @@ impl Parser {
                     .filter(|c| *c != A_INSTRUCTION_TOKEN)
                     .collect(),
             )),
-            InstructionType::L => Ok(Some(
-                self.current_instruction
-                    .clone()
-                    .unwrap()
-                    .chars()
-                    .filter(|c| *c != L_INSTRUCTION_TOKEN_START && *c != L_INSTRUCTION_TOKEN_END)
-                    .collect(),
-            )),
+            InstructionType::L => {
+                let instruction = self.current_instruction.as_ref().unwrap();
+                match numeric_label(instruction) {
+                    Some(label) => Ok(Some(label.to_string())),
+                    None => {
+                        let label: String = instruction
+                            .chars()
+                            .filter(|c| {
+                                *c != L_INSTRUCTION_TOKEN_START && *c != L_INSTRUCTION_TOKEN_END
+                            })
+                            .collect();
+                        // 数値ローカルラベルは`1:`とだけ書き、`(1)`のような数字で始まる名前は受け付けない
+                        if label.starts_with(|c: char| c.is_ascii_digit()) {
+                            bail!(ErrorCode::InvalidInstruction
+                                .error(tr!(invalid_label_name, instruction)));
+                        }
+                        Ok(Some(label))
+                    }
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn directive(&self) -> Result<Option<Directive>> {
+        if self.instruction_type()? != Some(InstructionType::Directive) {
+            return Ok(None);
+        }
+        let instruction = self.current_instruction.as_ref().unwrap();
+        let mut words = instruction.split_whitespace();
+        match words.next() {
+            Some(token) if EQU_DIRECTIVE_TOKENS.contains(&token) => {
+                match (words.next(), words.collect::<Vec<_>>().join(" ")) {
+                    (Some(name), value) if !value.is_empty() => Ok(Some(Directive::Equ {
+                        name: name.to_string(),
+                        value,
+                    })),
+                    _ => {
+                        bail!(ErrorCode::MalformedDirective
+                            .error(tr!(requires_name_and_value, token)))
+                    }
+                }
+            }
+            Some(VAR_DIRECTIVE_TOKEN) => {
+                match (words.next(), words.collect::<Vec<_>>().join(" ")) {
+                    (Some(name), size) => Ok(Some(Directive::Var {
+                        name: name.to_string(),
+                        size: Some(size).filter(|size| !size.is_empty()),
+                    })),
+                    _ => bail!(ErrorCode::MalformedDirective
+                        .error(tr!(requires_name, VAR_DIRECTIVE_TOKEN))),
+                }
+            }
+            Some(ORG_DIRECTIVE_TOKEN) => {
+                match words.collect::<Vec<_>>().join(" ") {
+                    address if !address.is_empty() => Ok(Some(Directive::Org { address })),
+                    _ => bail!(ErrorCode::MalformedDirective
+                        .error(tr!(requires_address, ORG_DIRECTIVE_TOKEN))),
+                }
+            }
+            Some(RECORD_DIRECTIVE_TOKEN) => {
+                let name = words.next();
+                let fields: Vec<(String, Option<String>)> = words
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .split(LIST_SEPARATOR)
+                    .map(|field| {
+                        let mut field = field.split_whitespace();
+                        let name = field.next().unwrap_or("").to_string();
+                        let size = Some(field.collect::<Vec<_>>().join(" "))
+                            .filter(|size| !size.is_empty());
+                        (name, size)
+                    })
+                    .collect();
+                match name {
+                    Some(name) if fields.iter().all(|(field, _)| !field.is_empty()) => {
+                        Ok(Some(Directive::Record {
+                            name: name.to_string(),
+                            fields,
+                        }))
+                    }
+                    _ => bail!(ErrorCode::MalformedDirective.error(tr!(
+                        requires_fields,
+                        RECORD_DIRECTIVE_TOKEN,
+                        LIST_SEPARATOR
+                    ))),
+                }
+            }
+            Some(DATA_DIRECTIVE_TOKEN) => {
+                let name = words.next();
+                let values: Vec<String> = words
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .split(LIST_SEPARATOR)
+                    .map(|value| value.trim().to_string())
+                    .collect();
+                match name {
+                    Some(name) if values.iter().all(|value| !value.is_empty()) => {
+                        Ok(Some(Directive::Data {
+                            name: name.to_string(),
+                            values,
+                        }))
+                    }
+                    _ => bail!(ErrorCode::MalformedDirective.error(tr!(
+                        requires_values,
+                        DATA_DIRECTIVE_TOKEN,
+                        LIST_SEPARATOR
+                    ))),
+                }
+            }
+            Some(STRING_DIRECTIVE_TOKEN) => {
+                let rest = instruction[STRING_DIRECTIVE_TOKEN.len()..].trim_start();
+                match rest.split_once(char::is_whitespace) {
+                    Some((name, text)) => Ok(Some(Directive::String {
+                        name: name.to_string(),
+                        text: string_literal(text.trim())?,
+                    })),
+                    None => bail!(ErrorCode::MalformedDirective
+                        .error(tr!(requires_string, STRING_DIRECTIVE_TOKEN))),
+                }
+            }
+            _ => {
+                bail!(ErrorCode::UnknownDirective.error(tr!(unknown_directive, instruction)))
+            }
+        }
+    }
+
+    // A命令のオペランドを式として解析する
+    pub fn expression(&self) -> Result<Option<Expression>> {
+        match self.instruction_type()? {
+            Some(InstructionType::A) => Ok(Some(expression::parse(&self.symbol()?.unwrap())?)),
             _ => Ok(None),
         }
     }
@@ -164,7 +577,11 @@ impl Parser {
 
 #[cfg(test)]
 mod tests {
-    use std::{fs, io::Write, path::Path};
+    use std::{
+        fs::{self, File},
+        io::Write,
+        path::Path,
+    };
 
     use super::*;
     use rand::distr::{Alphanumeric, SampleString};
@@ -181,16 +598,41 @@ mod tests {
     }
 
     #[test]
-    fn test_constructor() {
+    fn test_constructor() -> Result<()> {
         let test_file = create_test_file("");
-        let parser = Parser::new(&test_file);
+        let parser = Parser::new(&test_file)?;
         parser
             .assembly
-            .lines()
-            .into_iter()
-            .for_each(|line| println!("{}", line.unwrap()));
+            .iter()
+            .for_each(|line| println!("{}", line.text));
 
         let _ = fs::remove_file(test_file);
+
+        //読み込めないファイルはエラーにする
+        let error = Parser::new("../target/test/data/missing.asm")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "cannot read ../target/test/data/missing.asm"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_comment() {
+        assert_eq!(split_comment("D=M // load"), ("D=M ", Some("// load")));
+        assert_eq!(split_comment("// comment"), ("", Some("// comment")));
+        assert_eq!(split_comment("@'/'//slash"), ("@'/'", Some("//slash")));
+        assert_eq!(
+            split_comment(".string url \"http://a\\\"b\" // url"),
+            (".string url \"http://a\\\"b\" ", Some("// url"))
+        );
+        assert_eq!(split_comment("0;JMP"), ("0;JMP", None));
+
+        let lines = source_lines("@1 // one\n// hackasm:allow(unused-label)\n(END)//end");
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, vec!["@1", "// hackasm:allow(unused-label)", "(END)"]);
     }
 
     #[test]
@@ -198,7 +640,7 @@ mod tests {
         let file_content = "@123\n//this comment\n \n(START)\nD;JGT";
         let test_file = create_test_file(&file_content);
 
-        let mut parser = Parser::new(&test_file);
+        let mut parser = Parser::new(&test_file)?;
         let _ = fs::remove_file(test_file);
 
         //@123
@@ -220,7 +662,7 @@ mod tests {
     fn test_advance() -> Result<()> {
         let file_content = "@123\n//this comment\n \n(START)\nD;JGT";
         let test_file = create_test_file(&file_content);
-        let mut parser = Parser::new(&test_file);
+        let mut parser = Parser::new(&test_file)?;
         let _ = fs::remove_file(test_file);
 
         //次の命令を読み込む
@@ -245,7 +687,7 @@ mod tests {
     fn test_instruction() -> Result<()> {
         let file_content = "@123\n//this comment\n \n(START)\nD;JGT";
         let test_file = create_test_file(&file_content);
-        let mut parser = Parser::new(&test_file);
+        let mut parser = Parser::new(&test_file)?;
         let _ = fs::remove_file(test_file);
 
         //次の命令を読み込む
@@ -271,7 +713,7 @@ mod tests {
     fn test_symbol() -> Result<()> {
         let file_content = "@123\n//this comment\n \n(START)\n@sum\nD;JGT";
         let test_file = create_test_file(&file_content);
-        let mut parser = Parser::new(&test_file);
+        let mut parser = Parser::new(&test_file)?;
         let _ = fs::remove_file(test_file);
 
         //@123
@@ -285,6 +727,10 @@ mod tests {
         //sum
         parser.advance()?;
         assert_eq!(parser.symbol()?.unwrap(), "sum");
+        assert_eq!(
+            parser.expression()?.unwrap(),
+            Expression::Symbol("sum".to_string())
+        );
 
         //D;JGT
         parser.advance()?;
@@ -293,11 +739,218 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_from_source_location() -> Result<()> {
+        let mut parser = Parser::from_source("// comment\n@1\n\n  D=A\nfoo");
+
+        parser.advance()?;
+        assert_eq!(parser.symbol()?.unwrap(), "1");
+        assert_eq!(parser.location().line, 2);
+
+        //位置は字下げを除いた命令全体の範囲を持つ
+        parser.advance()?;
+        assert_eq!(parser.comp()?, "A");
+        assert_eq!(
+            parser.location(),
+            Location {
+                span: Some(Span { start: 3, end: 6 }),
+                ..Location::new(4)
+            }
+        );
+
+        parser.advance()?;
+        assert_eq!(parser.location().to_string(), "line 5");
+        assert!(parser.instruction_type().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbol_location() -> Result<()> {
+        let mut parser = Parser::from_source("  @X1+X\n(Main.loop)\n@'é'+LED");
+        let span = |location: Location| location.span.map(|span| (span.start, span.end));
+
+        //X1の一部ではなく、トークンとしてのXを指す
+        parser.advance()?;
+        assert_eq!(span(parser.symbol_location("X")), Some((7, 8)));
+        assert_eq!(span(parser.symbol_location("X1")), Some((4, 6)));
+        assert_eq!(parser.symbol_location("X").line, 1);
+        //見つからなければ命令全体
+        assert_eq!(span(parser.symbol_location("Y")), Some((3, 8)));
+
+        parser.advance()?;
+        assert_eq!(span(parser.symbol_location("Main.loop")), Some((2, 11)));
+
+        //列は文字単位で数える
+        parser.advance()?;
+        assert_eq!(span(parser.symbol_location("LED")), Some((6, 9)));
+
+        let mut parser = Parser::from_source(" AM=M+1;JNE");
+        parser.advance()?;
+        assert_eq!(span(parser.dest_location()), Some((2, 4)));
+        assert_eq!(span(parser.comp_location()), Some((5, 8)));
+        assert_eq!(span(parser.jump_location()), Some((9, 12)));
+
+        //マクロ展開で生成された行は列を持たない
+        let location = Location {
+            expansion: Some(Box::new(Expansion {
+                name: "M".to_string(),
+                call_site: Location::new(9),
+            })),
+            ..Location::new(2)
+        };
+        let text = "@X".to_string();
+        let mut parser = Parser::from_lines(vec![SourceLine { text, location }]);
+        parser.advance()?;
+        assert_eq!(span(parser.symbol_location("X")), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_label() -> Result<()> {
+        let mut parser = Parser::from_source("1:\n@1b\n(WAIT:LOOP:1)\n:\n(123)\n(1abc)");
+
+        parser.advance()?;
+        assert_eq!(parser.instruction_type()?.unwrap(), InstructionType::L);
+        assert_eq!(parser.symbol()?.unwrap(), "1");
+
+        parser.advance()?;
+        assert_eq!(parser.symbol()?.unwrap(), "1b");
+
+        parser.advance()?;
+        assert_eq!(parser.symbol()?.unwrap(), "WAIT:LOOP:1");
+
+        parser.advance()?;
+        assert!(parser.instruction_type().is_err());
+
+        //括弧で囲んだ数字で始まる名前はラベルとして不正
+        parser.advance()?;
+        assert_eq!(parser.instruction_type()?.unwrap(), InstructionType::L);
+        assert_eq!(
+            parser.symbol().unwrap_err().to_string(),
+            "invalid label name: \"(123)\""
+        );
+        parser.advance()?;
+        assert!(parser.symbol().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_directive() -> Result<()> {
+        let mut parser = Parser::from_source(
+            ".equ WIDTH 512\n  .define HEIGHT 256\n@WIDTH\n.equ X\n.foo\n\
+             .var i\n.var buffer 2*32\n.var\n.org 0x100\n\
+             .record Point x, y, color 2\n.record Point x,,y\n\
+             .data table 1, -1, 'A'\n.string msg \"Hi, \\\"you\\\"\"\n.string msg Hi",
+        );
+
+        parser.advance()?;
+        assert_eq!(
+            parser.instruction_type()?.unwrap(),
+            InstructionType::Directive
+        );
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::Equ {
+                name: "WIDTH".to_string(),
+                value: "512".to_string()
+            }
+        );
+        assert_eq!(parser.symbol()?, None);
+
+        parser.advance()?;
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::Equ {
+                name: "HEIGHT".to_string(),
+                value: "256".to_string()
+            }
+        );
+
+        parser.advance()?;
+        assert_eq!(parser.directive()?, None);
+
+        parser.advance()?;
+        assert!(parser.directive().is_err());
+
+        parser.advance()?;
+        assert!(parser.directive().is_err());
+
+        parser.advance()?;
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::Var {
+                name: "i".to_string(),
+                size: None
+            }
+        );
+
+        parser.advance()?;
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::Var {
+                name: "buffer".to_string(),
+                size: Some("2*32".to_string())
+            }
+        );
+
+        parser.advance()?;
+        assert!(parser.directive().is_err());
+
+        parser.advance()?;
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::Org {
+                address: "0x100".to_string()
+            }
+        );
+
+        parser.advance()?;
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::Record {
+                name: "Point".to_string(),
+                fields: vec![
+                    ("x".to_string(), None),
+                    ("y".to_string(), None),
+                    ("color".to_string(), Some("2".to_string())),
+                ]
+            }
+        );
+
+        parser.advance()?;
+        assert!(parser.directive().is_err());
+
+        parser.advance()?;
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::Data {
+                name: "table".to_string(),
+                values: vec!["1".to_string(), "-1".to_string(), "'A'".to_string()]
+            }
+        );
+
+        parser.advance()?;
+        assert_eq!(
+            parser.directive()?.unwrap(),
+            Directive::String {
+                name: "msg".to_string(),
+                text: "Hi, \"you\"".to_string()
+            }
+        );
+
+        parser.advance()?;
+        assert!(parser.directive().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_dest_comp_jump() -> Result<()> {
         let file_content = "D=D+1;JLE\nDM=D|A;JLT\nD&A;JMP\nD=A";
         let test_file = create_test_file(&file_content);
-        let mut parser = Parser::new(&test_file);
+        let mut parser = Parser::new(&test_file)?;
         let _ = fs::remove_file(test_file);
 
         parser.advance()?;
@@ -0,0 +1,473 @@
+//! `.macro NAME args ... .endm`によるマクロの定義と展開
+//!
+//! マクロ本体では`%引数名`が呼び出し時の引数に置き換えられる。
+//! 本体で定義したラベルは展開ごとに`マクロ名:ラベル名:展開番号`へ名前を変えるので、
+//! 同じマクロを何度呼び出してもラベルが衝突しない。
+//!
+//! ```text
+//! .macro INC addr
+//!     @%addr
+//!     M=M+1
+//! .endm
+//!
+//!     INC counter
+//! ```
+
+use crate::{cst, Expansion, Location, SourceLine};
+use anyhow::{anyhow, bail, Context, Result};
+use error_code::ErrorCode;
+use message::tr;
+use std::collections::HashMap;
+
+const MACRO_TOKEN: &str = ".macro";
+const END_MACRO_TOKEN: &str = ".endm";
+const PARAMETER_TOKEN: char = '%';
+const ARGUMENT_SEPARATOR: char = ',';
+pub const LOCAL_LABEL_SEPARATOR: char = ':';
+// 再帰呼び出しによる無限展開を防ぐための展開の深さの上限
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<SourceLine>,
+    // 本体で定義されているラベル
+    labels: Vec<String>,
+}
+
+// マクロ定義を取り除き、マクロ呼び出しを本体に展開した行を返す
+pub fn expand(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>> {
+    let (macros, lines) = collect_definitions(lines)?;
+    let mut expander = Expander {
+        macros,
+        expansion_count: 0,
+    };
+    let mut expanded = Vec::new();
+    expander.expand_lines(lines, 0, &mut expanded)?;
+
+    Ok(expanded)
+}
+
+fn collect_definitions(
+    lines: Vec<SourceLine>,
+) -> Result<(HashMap<String, Macro>, Vec<SourceLine>)> {
+    let mut macros: HashMap<String, Macro> = HashMap::new();
+    let mut rest = Vec::new();
+    let mut lines = lines.into_iter();
+
+    while let Some(line) = lines.next() {
+        let mut words = line.text.split_whitespace();
+        match words.next() {
+            Some(MACRO_TOKEN) => {
+                let location = line.location.clone();
+                let (name, parameters) =
+                    parse_signature(words.collect()).with_context(|| location.clone())?;
+                if macros.contains_key(&name) {
+                    return Err(anyhow!(
+                        ErrorCode::DuplicateMacro.error(tr!(duplicate_macro, name))
+                    ))
+                    .with_context(|| location);
+                }
+                let mut body = Vec::new();
+                loop {
+                    match lines.next() {
+                        Some(line) => match line.text.split_whitespace().next() {
+                            Some(END_MACRO_TOKEN) => break,
+                            Some(MACRO_TOKEN) => {
+                                return Err(anyhow!(
+                                    ErrorCode::MalformedMacro.error(tr!(nested_macro, name))
+                                ))
+                                .with_context(|| line.location);
+                            }
+                            _ => body.push(line),
+                        },
+                        None => {
+                            return Err(anyhow!(ErrorCode::MalformedMacro.error(tr!(
+                                missing_end_macro,
+                                name,
+                                END_MACRO_TOKEN
+                            ))))
+                            .with_context(|| location);
+                        }
+                    }
+                }
+                let labels = body
+                    .iter()
+                    .filter_map(|line| label_name(line.text.trim()))
+                    .map(str::to_string)
+                    .collect();
+                macros.insert(
+                    name,
+                    Macro {
+                        parameters,
+                        body,
+                        labels,
+                    },
+                );
+            }
+            Some(END_MACRO_TOKEN) => {
+                return Err(anyhow!(ErrorCode::MalformedMacro.error(tr!(
+                    end_macro_without_macro,
+                    END_MACRO_TOKEN,
+                    MACRO_TOKEN
+                ))))
+                .with_context(|| line.location);
+            }
+            _ => rest.push(line),
+        }
+    }
+
+    Ok((macros, rest))
+}
+
+// `.macro`に続くマクロ名と引数名を読み取る。引数名は`,`か空白で区切る
+fn parse_signature(words: Vec<&str>) -> Result<(String, Vec<String>)> {
+    let mut names: Vec<String> = words
+        .iter()
+        .flat_map(|word| word.split(ARGUMENT_SEPARATOR))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    if names.is_empty() {
+        bail!(ErrorCode::MalformedMacro.error(tr!(requires_macro_name, MACRO_TOKEN)));
+    }
+    let name = names.remove(0);
+    for parameter in names.iter() {
+        if !is_parameter_name(parameter) {
+            bail!(ErrorCode::MalformedMacro.error(tr!(invalid_parameter, parameter, name)));
+        }
+        if names.iter().filter(|p| *p == parameter).count() > 1 {
+            bail!(ErrorCode::MalformedMacro.error(tr!(duplicate_parameter, parameter, name)));
+        }
+    }
+
+    Ok((name, names))
+}
+
+// 呼び出しの引数を`,`で区切る。`SCREEN + 32`のように空白を含む式や、`' '`や`','`の文字リテラルも1つの引数になる
+fn split_arguments(v: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    while end < v.len() {
+        let (kind, len) = cst::next_token(&v[end..]);
+        if kind == cst::TokenKind::Comma {
+            arguments.push(&v[start..end]);
+            start = end + len;
+        }
+        end += len;
+    }
+    arguments.push(&v[start..]);
+    arguments
+        .into_iter()
+        .map(str::trim)
+        .filter(|argument| !argument.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_parameter_name(v: &str) -> bool {
+    !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ['_', '.', '$', ':'].contains(&c)
+}
+
+fn label_name(instruction: &str) -> Option<&str> {
+    instruction
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(')'))
+        .filter(|name| name.chars().all(is_symbol_char))
+}
+
+struct Expander {
+    macros: HashMap<String, Macro>,
+    expansion_count: usize,
+}
+
+impl Expander {
+    fn expand_lines(
+        &mut self,
+        lines: Vec<SourceLine>,
+        depth: usize,
+        expanded: &mut Vec<SourceLine>,
+    ) -> Result<()> {
+        for line in lines {
+            let text = line.text.trim_start();
+            match text.split_whitespace().next() {
+                Some(name) if self.macros.contains_key(name) => {
+                    if depth >= MAX_EXPANSION_DEPTH {
+                        return Err(anyhow!(
+                            ErrorCode::MacroTooDeep.error(tr!(macro_too_deep, name))
+                        ))
+                        .with_context(|| line.location.clone());
+                    }
+                    let arguments = split_arguments(&text[name.len()..]);
+                    let body = self.instantiate(name, &arguments, &line.location)?;
+                    self.expand_lines(body, depth + 1, expanded)?;
+                }
+                _ => expanded.push(line),
+            }
+        }
+
+        Ok(())
+    }
+
+    // ラベルを展開ごとの名前に変え、引数を置き換えたマクロ本体を返す。
+    // 引数として渡されたシンボルを書き換えないよう、ラベルの名前を先に変える
+    fn instantiate(
+        &mut self,
+        name: &str,
+        arguments: &[String],
+        call_site: &Location,
+    ) -> Result<Vec<SourceLine>> {
+        let definition = &self.macros[name];
+        if arguments.len() != definition.parameters.len() {
+            return Err(anyhow!(ErrorCode::MacroArguments.error(tr!(
+                macro_arguments,
+                name,
+                definition.parameters.len(),
+                arguments.len()
+            ))))
+            .with_context(|| call_site.clone());
+        }
+        self.expansion_count += 1;
+        let bindings: HashMap<&str, &str> = definition
+            .parameters
+            .iter()
+            .map(String::as_str)
+            .zip(arguments.iter().map(String::as_str))
+            .collect();
+
+        definition
+            .body
+            .iter()
+            .map(|line| {
+                let location = Location {
+                    expansion: Some(Box::new(Expansion {
+                        name: name.to_string(),
+                        call_site: call_site.clone(),
+                    })),
+                    ..line.location.clone()
+                };
+                let mut text = line.text.clone();
+                for label in definition.labels.iter() {
+                    let local_label = format!(
+                        "{}{sep}{}{sep}{}",
+                        name,
+                        label,
+                        self.expansion_count,
+                        sep = LOCAL_LABEL_SEPARATOR
+                    );
+                    text = replace_symbol(&text, label, &local_label);
+                }
+                let text =
+                    substitute_parameters(&text, &bindings).with_context(|| location.clone())?;
+                Ok(SourceLine { text, location })
+            })
+            .collect()
+    }
+}
+
+fn substitute_parameters(text: &str, bindings: &HashMap<&str, &str>) -> Result<String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find(PARAMETER_TOKEN) {
+        result.push_str(&rest[..index]);
+        let after = &rest[index + 1..];
+        let length = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        let parameter = &after[..length];
+        match bindings.get(parameter) {
+            Some(argument) => result.push_str(argument),
+            None => bail!(ErrorCode::MacroArguments.error(tr!(
+                unknown_parameter,
+                PARAMETER_TOKEN,
+                parameter
+            ))),
+        }
+        rest = &after[length..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+// シンボルの区切りに一致する箇所だけを置き換える。`%引数名`の引数名は置き換えない
+fn replace_symbol(text: &str, from: &str, to: &str) -> String {
+    let mut result = String::new();
+    let mut symbol = String::new();
+    for c in text.chars().chain(std::iter::once('\n')) {
+        if is_symbol_char(c) {
+            symbol.push(c);
+            continue;
+        }
+        let is_parameter = result.ends_with(PARAMETER_TOKEN);
+        result.push_str(if symbol == from && !is_parameter {
+            to
+        } else {
+            &symbol
+        });
+        symbol.clear();
+        result.push(c);
+    }
+    result.pop();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source_lines;
+
+    fn texts(lines: &[SourceLine]) -> Vec<&str> {
+        lines.iter().map(|line| line.text.trim()).collect()
+    }
+
+    #[test]
+    fn test_expand() -> Result<()> {
+        let source = ".macro PUSH_D\n@SP\nA=M\nM=D\n@SP\nM=M+1\n.endm\nD=A\nPUSH_D\nPUSH_D";
+        let expanded = expand(source_lines(source))?;
+        assert_eq!(
+            texts(&expanded),
+            vec!["D=A", "@SP", "A=M", "M=D", "@SP", "M=M+1", "@SP", "A=M", "M=D", "@SP", "M=M+1"]
+        );
+
+        //展開された行はマクロ本体の行番号と呼び出し元の行番号を持つ
+        let location = &expanded[1].location;
+        assert_eq!(location.line, 2);
+        assert_eq!(
+            location.expansion.as_ref().unwrap().call_site,
+            Location::new(9)
+        );
+        assert_eq!(
+            expanded[6].location.to_string(),
+            "line 2 (in expansion of macro PUSH_D at line 10)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_parameters_and_labels() -> Result<()> {
+        let source = "\
+.macro WAIT addr, value
+(LOOP)
+    @%addr
+    D=M
+    @%value
+    D=D-A
+    @LOOP
+    D;JNE
+.endm
+    WAIT KBD, 65
+    WAIT KBD,66";
+        let expanded = expand(source_lines(source))?;
+        assert_eq!(
+            texts(&expanded),
+            vec![
+                "(WAIT:LOOP:1)",
+                "@KBD",
+                "D=M",
+                "@65",
+                "D=D-A",
+                "@WAIT:LOOP:1",
+                "D;JNE",
+                "(WAIT:LOOP:2)",
+                "@KBD",
+                "D=M",
+                "@66",
+                "D=D-A",
+                "@WAIT:LOOP:2",
+                "D;JNE",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_spaced_arguments() -> Result<()> {
+        let source =
+            ".macro LOAD x, y\n@%x\nD=A\n@%y\n.endm\nLOAD SCREEN + 32, ' '\nLOAD ',' , (1 + 2) * 3";
+        let expanded = expand(source_lines(source))?;
+        assert_eq!(
+            texts(&expanded),
+            vec!["@SCREEN + 32", "D=A", "@' '", "@','", "D=A", "@(1 + 2) * 3"]
+        );
+        assert_eq!(split_arguments(" a ,b,  c d "), vec!["a", "b", "c d"]);
+        assert!(split_arguments("  ").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_argument_named_like_label() -> Result<()> {
+        // 呼び出し元のラベルを引数に渡しても、本体のラベルの名前に変えない
+        let source = "\
+.macro WAIT target
+(LOOP)
+    @%target
+    0;JMP
+.endm
+(LOOP)
+    WAIT LOOP";
+        let expanded = expand(source_lines(source))?;
+        assert_eq!(
+            texts(&expanded),
+            vec!["(LOOP)", "(WAIT:LOOP:1)", "@LOOP", "0;JMP"]
+        );
+
+        // 本体のラベルと同じ名前の引数も置き換えられる
+        let source = ".macro JUMP LOOP\n(LOOP)\n@%LOOP\n0;JMP\n.endm\nJUMP END";
+        let expanded = expand(source_lines(source))?;
+        assert_eq!(texts(&expanded), vec!["(JUMP:LOOP:1)", "@END", "0;JMP"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_nested() -> Result<()> {
+        let source = ".macro INC addr\n@%addr\nM=M+1\n.endm\n.macro INC2 addr\nINC %addr\nINC %addr\n.endm\nINC2 i";
+        let expanded = expand(source_lines(source))?;
+        assert_eq!(texts(&expanded), vec!["@i", "M=M+1", "@i", "M=M+1"]);
+        assert_eq!(
+            expanded[0].location.to_string(),
+            "line 2 (in expansion of macro INC at line 6 (in expansion of macro INC2 at line 9))"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let error = |source: &str| format!("{:#}", expand(source_lines(source)).unwrap_err());
+
+        assert_eq!(error(".macro A\n@1"), "line 1: macro A is missing .endm");
+        assert_eq!(error("@1\n.endm"), "line 2: .endm without .macro");
+        assert_eq!(
+            error(".macro A\n.endm\n.macro A\n.endm"),
+            "line 3: macro A is already defined"
+        );
+        assert_eq!(
+            error(".macro A x\n@%x\n.endm\nA"),
+            "line 4: macro A takes 1 argument(s) but 0 were given"
+        );
+        assert_eq!(
+            error(".macro A x\n@%y\n.endm\nA 1"),
+            "line 2 (in expansion of macro A at line 4): unknown macro parameter %y"
+        );
+        assert!(error(".macro A\nA\n.endm\nA").ends_with("is too deep (recursive macro?)"));
+    }
+
+    #[test]
+    fn test_replace_symbol() {
+        assert_eq!(replace_symbol("@LOOP", "LOOP", "X"), "@X");
+        assert_eq!(replace_symbol("(LOOP)", "LOOP", "X"), "(X)");
+        assert_eq!(replace_symbol("@LOOP2", "LOOP", "X"), "@LOOP2");
+        assert_eq!(replace_symbol("@MAIN.LOOP", "LOOP", "X"), "@MAIN.LOOP");
+        assert_eq!(replace_symbol("@%LOOP", "LOOP", "X"), "@%LOOP");
+    }
+}
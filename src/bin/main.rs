@@ -1,133 +1,371 @@
-use anyhow::Result;
-use clap::Parser;
-use parser::InstructionType;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use assembler::rules::{Diagnostic, Level, LintLevels, Rule};
+use clap::{Parser, Subcommand, ValueEnum};
+use message::{tr, Language};
+use output::report::{Report, Severity};
+use parser::Location;
+use std::fs;
+use std::path::{Path, PathBuf};
 use symbol_table::SymbolTable;
 
-#[derive(Debug, Parser)]
-#[command(author, version, about)]
+#[derive(Debug, Default, Parser)]
+// -fは--explainとサブコマンドがなければ必須なので、使い方の行に明示する
+#[command(
+    author,
+    version,
+    about,
+    subcommand_negates_reqs = true,
+    override_usage = "HackAssembler [OPTIONS] -f <FILE_NAME.asm>
+       HackAssembler --explain <CODE>
+       HackAssembler fmt [OPTIONS] <FILE_NAME.asm>..."
+)]
 pub struct Arg {
     // HackAsembler File Path
-    #[arg(value_name = "FILE_NAME.asm", short)]
-    file: String,
+    #[arg(
+        value_name = "FILE_NAME.asm",
+        short,
+        required_unless_present = "explain"
+    )]
+    file: Option<String>,
+    // エラー番号(H0003など)の説明を表示する
+    #[arg(long, value_name = "CODE")]
+    explain: Option<String>,
+    // 出力形式
+    #[arg(long, value_enum, default_value_t = OutputFormat::Hack)]
+    format: OutputFormat,
+    // .includeで取り込むファイルの検索パス
+    #[arg(short = 'I', value_name = "DIR")]
+    include_paths: Vec<PathBuf>,
+    // シンボルの一覧を.symファイルに出力する
+    #[arg(long)]
+    symbol_map: bool,
+    // .varで宣言していないシンボルを変数として使うとエラーにする
+    #[arg(long)]
+    strict: bool,
+    // .data/.stringで宣言したRAMの初期値を.ramファイルに出力する
+    #[arg(long)]
+    ram_image: bool,
+    // .data/.stringの初期値をRAMに書き込むコードを0番地に置く
+    #[arg(long)]
+    init_prologue: bool,
+    // 定義済みシンボルと変数の割り当てを設定するTOMLファイル
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    // 定義済みシンボルを追加・上書きする
+    #[arg(short = 'D', value_name = "NAME=ADDRESS", value_parser = config::parse_define)]
+    defines: Vec<(String, u16)>,
+    // 変数を割り当て始めるRAMアドレス(既定は16)
+    #[arg(long, value_name = "ADDRESS", value_parser = config::parse_address)]
+    variable_base: Option<u16>,
+    // 誤りの可能性が高い命令の並びを警告する
+    #[arg(long)]
+    lint: bool,
+    // 規則の警告を出さない
+    #[arg(long, value_name = "RULE")]
+    allow: Vec<Rule>,
+    // 規則を警告にする
+    #[arg(long, value_name = "RULE")]
+    warn: Vec<Rule>,
+    // 規則をエラーにする
+    #[arg(long, value_name = "RULE")]
+    deny: Vec<Rule>,
+    // 警告をすべてエラーにする
+    #[arg(long)]
+    deny_warnings: bool,
+    // 警告とエラーの出力形式
+    #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+    // 警告とエラーをSARIF形式で書き出すファイル
+    #[arg(long, value_name = "FILE")]
+    sarif: Option<PathBuf>,
+    // メッセージの言語(enまたはja)。省略すると環境変数LANGなどから決める
+    #[arg(long, value_name = "LANG")]
+    lang: Option<Language>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() -> Result<()> {
-    if let Err(e) = hack_assembler(&Arg::parse()) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    }
-    Ok(())
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// .asmファイルを決まった形に整形する
+    Fmt {
+        // 書き換えずに、整形されていないファイルがあれば失敗する
+        #[arg(long)]
+        check: bool,
+        #[arg(value_name = "FILE_NAME.asm", required = true)]
+        files: Vec<PathBuf>,
+    },
 }
 
-fn hack_assembler(config: &Arg) -> Result<String> {
-    let asm_file = Path::new(config.file.as_str());
-    let mut symbol_table = SymbolTable::new();
-    first_pass(asm_file.to_string_lossy().to_string(), &mut symbol_table)?;
-    let hack_file_path = second_pass(asm_file.to_string_lossy().to_string(), &mut symbol_table)?;
-
-    println!("Assembled: {}", &hack_file_path);
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    /// 人が読むための1行のテキスト
+    #[default]
+    Human,
+    /// 1件ごとに1行のJSON
+    Json,
+}
 
-    Ok(hack_file_path)
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// nand2tetris形式の.hackファイル
+    #[default]
+    Hack,
+    /// static const uint16_t rom[]を定義するCのヘッダファイル
+    C,
+    /// pub const ROM: [u16; N]を定義するRustのモジュール
+    Rust,
 }
 
-fn first_pass(asm_file_path: String, symbol_table: &mut SymbolTable) -> Result<()> {
-    let mut asm_parser = parser::Parser::new(asm_file_path.as_str());
-    let mut row_number: u16 = 0;
+impl OutputFormat {
+    fn extension(&self) -> &str {
+        match self {
+            OutputFormat::Hack => "hack",
+            OutputFormat::C => "h",
+            OutputFormat::Rust => "rs",
+        }
+    }
+}
 
-    while asm_parser.has_more_lines()? {
-        asm_parser.advance()?;
-        match asm_parser.instruction_type()? {
-            Some(InstructionType::L) => {
-                symbol_table.add_entry(asm_parser.symbol()?.unwrap().as_str(), row_number)?;
+fn main() -> Result<()> {
+    let config = Arg::parse();
+    message::set_language(config.lang.unwrap_or_else(Language::from_env));
+    if let Some(code) = &config.explain {
+        match error_code::explain(code) {
+            Ok(explanation) => print!("{}", explanation),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    if let Some(Command::Fmt { check, files }) = &config.command {
+        if !format_files(files, *check)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let mut reports = Vec::new();
+    let result = hack_assembler(&config, &mut reports);
+    let error = result.as_ref().err().map(error_report);
+    match config.message_format {
+        MessageFormat::Human => {
+            for report in reports.iter() {
+                eprintln!("{}", report);
             }
-            Some(InstructionType::A) | Some(InstructionType::C) => {
-                row_number += 1;
+            if let Err(e) = &result {
+                match error_code::code_of(e) {
+                    Some(code) => eprintln!("{:#} [{}]", e, code),
+                    None => eprintln!("{:#}", e),
+                }
             }
-            None => (),
         }
+        MessageFormat::Json => {
+            for report in reports.iter().chain(error.iter()) {
+                eprintln!("{}", output::report::json(report));
+            }
+        }
+    }
+    if let Some(path) = &config.sarif {
+        reports.extend(error);
+        let log =
+            output::report::sarif(&reports, env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
+        fs::write(path, log).with_context(|| tr!(cannot_write, path.display()))?;
+    }
+    if result.is_err() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-        if !(asm_parser.has_more_lines()?) {
-            break;
+// ファイルを整形する。checkでは書き換えず、整形されていないファイルがあればfalseを返す
+fn format_files(files: &[PathBuf], check: bool) -> Result<bool> {
+    let mut formatted = true;
+    for file in files.iter() {
+        let source = fs::read_to_string(file).with_context(|| tr!(cannot_read, file.display()))?;
+        let result = formatter::format(&source);
+        if result == source {
+            continue;
+        }
+        if check {
+            // 最初に異なる行を示す
+            let line = formatter::first_difference(&source, &result);
+            println!("{}", tr!(not_formatted, file.display(), line));
+            formatted = false;
+        } else {
+            fs::write(file, result).with_context(|| tr!(cannot_write, file.display()))?;
+            println!("{}", tr!(formatted, file.display()));
         }
     }
+    Ok(formatted)
+}
 
-    Ok(())
+fn diagnostic_report(diagnostic: Diagnostic) -> Report {
+    Report {
+        severity: match diagnostic.level {
+            Level::Deny => Severity::Error,
+            _ => Severity::Warning,
+        },
+        code: Some(diagnostic.rule.id().to_string()),
+        message: diagnostic.message,
+        location: Some(diagnostic.location),
+    }
+}
+
+// エラーに位置が付いていれば、メッセージから位置を外して別に持つ
+fn error_report(error: &anyhow::Error) -> Report {
+    let location = error.downcast_ref::<Location>().cloned();
+    let message = format!("{:#}", error);
+    let message = match &location {
+        Some(location) => message
+            .strip_prefix(&format!("{}: ", location))
+            .unwrap_or(&message)
+            .to_string(),
+        None => message,
+    };
+    Report {
+        severity: Severity::Error,
+        code: error_code::code_of(error).map(|code| code.code()),
+        message,
+        location,
+    }
 }
 
-fn second_pass(asm_file_path: String, symbol_table: &mut SymbolTable) -> Result<String> {
-    let asm_file = Path::new(asm_file_path.as_str());
+// 設定ファイル、コマンドラインの順に定義済みシンボルを上書きする
+fn predefined_symbols(config: &Arg, file_config: &config::Config) -> SymbolTable {
+    let mut symbol_table = if file_config.replace_predefined {
+        SymbolTable::empty()
+    } else {
+        SymbolTable::new()
+    };
+    for (name, address) in file_config.predefined.iter() {
+        symbol_table.add_predefined(name, *address);
+    }
+    for (name, address) in config.defines.iter() {
+        symbol_table.add_predefined(name, *address);
+    }
+    symbol_table
+}
+
+// 既定値、--lint、設定ファイル、コマンドラインの順に規則の水準を上書きする
+fn lint_levels(config: &Arg, file_config: &config::Config) -> Result<LintLevels> {
+    let mut levels = LintLevels::default();
+    if config.lint {
+        levels.enable_lints();
+    }
+    for (rule, level) in file_config.lints.iter() {
+        levels.set(
+            rule.parse().with_context(|| tr!(invalid_lints_config))?,
+            level.parse().with_context(|| tr!(invalid_lints_config))?,
+        );
+    }
+    for (rules, level) in [
+        (&config.allow, Level::Allow),
+        (&config.warn, Level::Warn),
+        (&config.deny, Level::Deny),
+    ] {
+        for rule in rules.iter() {
+            levels.set(*rule, level);
+        }
+    }
+    levels.deny_warnings = config.deny_warnings;
+    Ok(levels)
+}
+
+// 警告はreportsに加える
+fn hack_assembler(config: &Arg, reports: &mut Vec<Report>) -> Result<String> {
+    // --explainとfmtを除き、-fはclapが必須にしている
+    let asm_file = Path::new(config.file.as_deref().unwrap());
+    let file_config = match &config.config {
+        Some(path) => config::load(path)?,
+        None => config::Config::default(),
+    };
+    let mut symbol_table = predefined_symbols(config, &file_config);
+    let levels = lint_levels(config, &file_config)?;
+    let lines = assembler::preprocess(parser::include::load(asm_file, &config.include_paths)?)?;
+    let default_options = assembler::Options::default();
+    let options = assembler::Options {
+        strict: config.strict,
+        init_prologue: config.init_prologue,
+        variable_base: config
+            .variable_base
+            .or(file_config.variable_base)
+            .unwrap_or(default_options.variable_base),
+    };
+    let definitions = assembler::first_pass(
+        &mut parser::Parser::from_lines(lines.clone()),
+        &mut symbol_table,
+        &options,
+    )?;
+    let labels = assembler::label_entries(&symbol_table);
+    let mut warnings = Vec::new();
+    let words = assembler::second_pass(
+        &mut parser::Parser::from_lines(lines.clone()),
+        &mut symbol_table,
+        &definitions,
+        &options,
+        &mut warnings,
+    )?;
+    warnings.extend(assembler::lint::lint(
+        &mut parser::Parser::from_lines(lines.clone()),
+        &symbol_table,
+        &definitions,
+    )?);
+    let diagnostics = assembler::rules::diagnostics(warnings, &levels, &lines)?;
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.level == Level::Deny)
+        .count();
+    reports.extend(diagnostics.into_iter().map(diagnostic_report));
+    if errors > 0 {
+        bail!(tr!(aborting, errors));
+    }
+
     let file_name = asm_file
         .file_stem()
-        .expect("get file_stem error")
+        .ok_or_else(|| anyhow!(tr!(no_file_name, asm_file)))?
         .to_string_lossy();
-    let hack_file_path = asm_file
+    let output_file_path = asm_file
         .parent()
-        .expect("get hack save dir failed.")
-        .join(format!("{}.{}", file_name, "hack"));
-    let mut hack_file = File::create(&hack_file_path).unwrap();
-    let mut asm_parser = parser::Parser::new(asm_file_path.as_str());
-    let mut variable_ram_address: u16 = 16;
-
-    while asm_parser.has_more_lines()? {
-        asm_parser.advance()?;
-        match asm_parser.instruction_type()? {
-            Some(parser::InstructionType::A) => {
-                //文字列であれば変数として扱い、数値であればそのままバイナリに変換して書き込む
-                let symbol = asm_parser.symbol()?.unwrap();
-                match symbol.parse::<u16>() {
-                    //数値に変換正羽したのでバイナリに変換して書き込む
-                    Ok(numeric_value) => {
-                        let binary_string = format_16bit_binary_string(numeric_value);
-                        hack_file.write(binary_string.as_bytes())?;
-                    }
-                    //数値に変換できなかったので変数として扱う
-                    Err(_) => {
-                        if symbol_table.contains(&symbol)? {
-                            let address = symbol_table.get_address(symbol.as_str())?;
-                            hack_file.write(format_16bit_binary_string(address).as_bytes())?;
-                        } else {
-                            symbol_table.add_entry(symbol.as_str(), variable_ram_address)?;
-                            hack_file.write(
-                                format_16bit_binary_string(variable_ram_address).as_bytes(),
-                            )?;
-                            variable_ram_address += 1;
-                        }
-                    }
-                }
-            }
-            Some(parser::InstructionType::C) => {
-                let dest_binary_string = code::Code::dest(asm_parser.dest()?)?;
-                let comp_binary_string = code::Code::comp(asm_parser.comp()?)?;
-                let jump_binary_string = code::Code::jump(asm_parser.jump()?)?;
-                let c_instruction_binary_string = format!(
-                    "111{}{}{}\n",
-                    comp_binary_string, dest_binary_string, jump_binary_string
-                );
-                hack_file.write(c_instruction_binary_string.as_bytes())?;
-            }
-            Some(parser::InstructionType::L) => (),
-            None => (),
-        }
-
-        if !(asm_parser.has_more_lines()?) {
-            break;
-        }
+        .ok_or_else(|| anyhow!(tr!(no_output_directory, asm_file)))?
+        .join(format!("{}.{}", file_name, config.format.extension()));
+    let contents = match config.format {
+        OutputFormat::Hack => output::hack(&words),
+        OutputFormat::C => output::c_header(&file_name, &words, &labels)?,
+        OutputFormat::Rust => output::rust_module(&words, &labels)?,
+    };
+    fs::write(&output_file_path, contents)
+        .with_context(|| tr!(cannot_write, output_file_path.display()))?;
+    if config.symbol_map {
+        let symbol_map_path = output_file_path.with_extension("sym");
+        fs::write(&symbol_map_path, output::symbol_map(&symbol_table))
+            .with_context(|| tr!(cannot_write, symbol_map_path.display()))?;
+        println!(
+            "{}",
+            tr!(symbol_map_written, symbol_map_path.to_string_lossy())
+        );
+    }
+    if config.ram_image {
+        let ram_image_path = output_file_path.with_extension("ram");
+        fs::write(&ram_image_path, output::ram_image(&definitions.ram_data()))
+            .with_context(|| tr!(cannot_write, ram_image_path.display()))?;
+        println!(
+            "{}",
+            tr!(ram_image_written, ram_image_path.to_string_lossy())
+        );
     }
+    let output_file_path = output_file_path.to_string_lossy().to_string();
 
-    Ok(hack_file_path.to_string_lossy().to_string())
-}
+    println!("{}", tr!(assembled, &output_file_path));
 
-fn format_16bit_binary_string(v: u16) -> String {
-    format!("{:016b}\n", v)
+    Ok(output_file_path)
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
-        fs,
+        fs::{self, File},
         io::{Read, Write},
         path::Path,
     };
@@ -156,9 +394,10 @@ mod tests {
     fn test_hack_assemble() -> Result<()> {
         let test_file = create_test_file("@40\nDM=A+1;JNE");
         let config = Arg {
-            file: test_file.clone(),
+            file: Some(test_file.clone()),
+            ..Default::default()
         };
-        let hack_file_path = hack_assembler(&config)?;
+        let hack_file_path = hack_assembler(&config, &mut Vec::new())?;
         let mut hack_file = File::open(hack_file_path)?;
         let mut buffer = String::new();
         let _ = hack_file.read_to_string(&mut buffer);
@@ -170,33 +409,209 @@ mod tests {
     }
 
     #[test]
-    fn test_first_pass() -> Result<()> {
-        let test_file_path = create_test_file("(START)\n@40\n(LOOP)\nDM=A+1;JNE\n(STOP)\n(END)\n");
-        let mut symbol_table = SymbolTable::new();
-        first_pass(test_file_path.clone(), &mut symbol_table)?;
+    fn test_hack_assemble_rust_format() -> Result<()> {
+        let test_file = create_test_file("(LOOP)\n@LOOP\n0;JMP\n(END)\n@i\nM=1");
+        let config = Arg {
+            file: Some(test_file.clone()),
+            format: OutputFormat::Rust,
+            ..Default::default()
+        };
+        let rust_file_path = hack_assembler(&config, &mut Vec::new())?;
+        assert!(rust_file_path.ends_with(".rs"));
+        let buffer = fs::read_to_string(&rust_file_path)?;
+        assert_eq!(
+            buffer,
+            "pub const LOOP: u16 = 0;\npub const END: u16 = 2;\n\n\
+             pub const ROM: [u16; 4] = [\n    0x0000, 0xEA87, 0x0010, 0xEFC8,\n];\n"
+        );
+
+        let _ = fs::remove_file(test_file);
+        let _ = fs::remove_file(rust_file_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hack_assemble_symbol_map() -> Result<()> {
+        let test_file = create_test_file(".equ WIDTH 32\n(LOOP)\n@WIDTH\n@i\n@LOOP\n0;JMP");
+        let config = Arg {
+            file: Some(test_file.clone()),
+            symbol_map: true,
+            ..Default::default()
+        };
+        let hack_file_path = hack_assembler(&config, &mut Vec::new())?;
+        let symbol_map_path = Path::new(&hack_file_path).with_extension("sym");
+        assert_eq!(
+            fs::read_to_string(&symbol_map_path)?,
+            "label          0 LOOP\nconstant      32 WIDTH\nvariable      16 i\n"
+        );
 
-        assert_eq!(symbol_table.get_address("START")?, 0);
-        assert_eq!(symbol_table.get_address("LOOP")?, 1);
-        assert_eq!(symbol_table.get_address("STOP")?, 2);
-        assert_eq!(symbol_table.get_address("END")?, 2);
+        let _ = fs::remove_file(test_file);
+        let _ = fs::remove_file(hack_file_path);
+        let _ = fs::remove_file(symbol_map_path);
 
-        let _ = fs::remove_file(test_file_path);
         Ok(())
     }
 
     #[test]
-    fn test_second_pass() -> Result<()> {
-        let test_file_path = create_test_file("(START)\n@40\n(LOOP)\nDM=A+1;JNE\n(STOP)\nD=A\n(END)\n");
-        let mut symbol_table = SymbolTable::new();
-        first_pass(test_file_path.clone(), &mut symbol_table)?;
-        second_pass(test_file_path.clone(), &mut symbol_table)?;
-
-        assert_eq!(symbol_table.get_address("START")?, 0);
-        assert_eq!(symbol_table.get_address("LOOP")?, 1);
-        assert_eq!(symbol_table.get_address("STOP")?, 2);
-        assert_eq!(symbol_table.get_address("END")?, 3);
-
-        let _ = fs::remove_file(test_file_path);
+    fn test_hack_assemble_include_error() -> Result<()> {
+        let lib_file = create_test_file("@1\nD=X");
+        let lib_name = Path::new(&lib_file).file_name().unwrap().to_string_lossy();
+        let test_file = create_test_file(&format!("@0\n.include \"{}\"", lib_name));
+        let config = Arg {
+            file: Some(test_file.clone()),
+            include_paths: vec![PathBuf::from("../target/test/data")],
+            ..Default::default()
+        };
+        let error = hack_assembler(&config, &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            format!("{}:2: unknown comp mnemonic: \"X\"", lib_file)
+        );
+
+        let _ = fs::remove_file(test_file);
+        let _ = fs::remove_file(lib_file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hack_assemble_ram_image() -> Result<()> {
+        let test_file = create_test_file(".string msg \"A\"\n@msg\nD=M");
+        let config = Arg {
+            file: Some(test_file.clone()),
+            ram_image: true,
+            init_prologue: true,
+            ..Default::default()
+        };
+        let hack_file_path = hack_assembler(&config, &mut Vec::new())?;
+        let ram_image_path = Path::new(&hack_file_path).with_extension("ram");
+        let ram_image = fs::read_to_string(&ram_image_path)?;
+        assert_eq!(ram_image.lines().count(), 18);
+        assert_eq!(ram_image.lines().nth(16), Some("0000000001000001"));
+        //初期化コード(2ワード分の書き込みとジャンプ)の後ろに利用者のコードが続く
+        let hack = fs::read_to_string(&hack_file_path)?;
+        assert_eq!(hack.lines().count(), 12);
+        assert_eq!(hack.lines().nth(8), Some("0000000000001010"));
+
+        let _ = fs::remove_file(test_file);
+        let _ = fs::remove_file(hack_file_path);
+        let _ = fs::remove_file(ram_image_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hack_assemble_config() -> Result<()> {
+        let config_file = create_test_file(
+            "replace_predefined = true\nvariable_base = 100\n[predefined]\nLED = 0x6001\nUART = 1",
+        );
+        let test_file = create_test_file("@LED\n@UART\n@TIMER\n@SCREEN");
+        let config = Arg {
+            file: Some(test_file.clone()),
+            config: Some(PathBuf::from(&config_file)),
+            defines: vec![("UART".to_string(), 0x6002), ("TIMER".to_string(), 0x6003)],
+            ..Default::default()
+        };
+        let hack_file_path = hack_assembler(&config, &mut Vec::new())?;
+        //コマンドラインの定義が設定ファイルより優先され、既定の定義済みシンボル(SCREEN)は変数になる
+        let words: Vec<u16> = fs::read_to_string(&hack_file_path)?
+            .lines()
+            .map(|line| u16::from_str_radix(line, 2).unwrap())
+            .collect();
+        assert_eq!(words, vec![0x6001, 0x6002, 0x6003, 100]);
+
+        let _ = fs::remove_file(test_file);
+        let _ = fs::remove_file(config_file);
+        let _ = fs::remove_file(hack_file_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hack_assemble_lint() -> Result<()> {
+        let test_file = create_test_file("(LOOP)\n@LOOP\nD=M;JGT");
+        let config = Arg {
+            file: Some(test_file.clone()),
+            lint: true,
+            ..Default::default()
+        };
+        //リントの警告があってもアセンブルは成功する
+        let hack_file_path = hack_assembler(&config, &mut Vec::new())?;
+        assert_eq!(fs::read_to_string(&hack_file_path)?.lines().count(), 2);
+
+        let _ = fs::remove_file(test_file);
+        let _ = fs::remove_file(hack_file_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hack_assemble_deny_warnings() -> Result<()> {
+        let source = "(START)\n// hackasm:allow(unused-label)\n(END)\n@END\n0;JMP";
+        let test_file = create_test_file(source);
+        let config = Arg {
+            file: Some(test_file.clone()),
+            deny_warnings: true,
+            ..Default::default()
+        };
+        let error = hack_assembler(&config, &mut Vec::new()).unwrap_err();
+        assert_eq!(format!("{:#}", error), "aborting due to 1 lint error(s)");
+
+        //コマンドラインの指定で規則を無効にできる
+        let config = Arg {
+            allow: vec![Rule::UnusedLabel],
+            ..config
+        };
+        let hack_file_path = hack_assembler(&config, &mut Vec::new())?;
+
+        let _ = fs::remove_file(test_file);
+        let _ = fs::remove_file(hack_file_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_report() -> Result<()> {
+        let test_file = create_test_file("@0\n  D=X");
+        let config = Arg {
+            file: Some(test_file.clone()),
+            ..Default::default()
+        };
+        let mut reports = Vec::new();
+        let error = hack_assembler(&config, &mut reports).unwrap_err();
+        let report = error_report(&error);
+        assert_eq!(report.message, "unknown comp mnemonic: \"X\"");
+        assert_eq!(report.code.as_deref(), Some("H0003"));
+        //誤りのあるcompだけを指す
+        let location = report.location.unwrap();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.span, Some(parser::Span { start: 5, end: 6 }));
+
+        let _ = fs::remove_file(test_file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hack_assemble_strict() -> Result<()> {
+        let test_file = create_test_file(".var counter\n@counter\nM=0\n@coutner\nM=M+1");
+        let config = Arg {
+            file: Some(test_file.clone()),
+            strict: true,
+            ..Default::default()
+        };
+        let error = hack_assembler(&config, &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            format!(
+                "{}:4: undeclared symbol coutner (did you mean counter?)",
+                test_file
+            )
+        );
+
+        let _ = fs::remove_file(test_file);
+
         Ok(())
     }
 }
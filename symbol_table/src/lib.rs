@@ -1,53 +1,242 @@
-use anyhow::Result;
-use std::collections::HashMap;
+use anyhow::{bail, Result};
+use error_code::ErrorCode;
+use message::tr;
+use parser::Location;
+use std::{collections::HashMap, fmt};
 
+// R0〜R15、仮想マシンのポインタ、メモリマップドI/Oのアドレス
+const PREDEFINED_SYMBOLS: [(&str, u16); 23] = [
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
+
+// 名前が似ているとみなす編集距離の上限
+const MAX_SIMILAR_DISTANCE: usize = 2;
+
+/// シンボルの種別
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SymbolKind {
+    /// R0〜R15やSCREENなどの定義済みシンボル
+    Predefined,
+    /// (LOOP)のように定義されたROMアドレス
+    Label,
+    /// .equ/.defineで定義された定数(RAMを割り当てない)
+    Constant,
+    /// .varで宣言した、またはA命令で初めて使われた時にRAMを割り当てた変数
+    Variable,
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            SymbolKind::Predefined => "predefined",
+            SymbolKind::Label => "label",
+            SymbolKind::Constant => "constant",
+            SymbolKind::Variable => "variable",
+        };
+        f.pad(kind)
+    }
+}
+
+impl SymbolKind {
+    /// メッセージに使う、現在の言語での種別の名前
+    pub fn description(&self) -> String {
+        match self {
+            SymbolKind::Predefined => tr!(kind_predefined),
+            SymbolKind::Label => tr!(kind_label),
+            SymbolKind::Constant => tr!(kind_constant),
+            SymbolKind::Variable => tr!(kind_variable),
+        }
+    }
+}
+
+/// シンボルの種別・値と、ソース上で定義・参照された位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// ラベルのROMアドレス、変数のRAMアドレス、または定数の値
+    pub address: u16,
+    /// 割り当てた領域のワード数(変数以外は1)
+    pub size: u16,
+    /// 定義した位置。定義済みシンボルと位置の分からない定義はNone
+    pub definition: Option<Location>,
+    /// 参照された位置(参照された順)
+    pub references: Vec<Location>,
+}
+
+/// 登録した順序を保つシンボル表
 pub struct SymbolTable {
-    pub entries: HashMap<String, u16>,
+    symbols: Vec<SymbolInfo>,
+    // シンボル名からsymbolsの添字への索引
+    indexes: HashMap<String, usize>,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
         //　定義済みのシンボルとアドレスを登録
-        let mut entries = HashMap::new();
-        entries.insert("R0".to_string(), 0);
-        entries.insert("R1".to_string(), 1);
-        entries.insert("R2".to_string(), 2);
-        entries.insert("R3".to_string(), 3);
-        entries.insert("R4".to_string(), 4);
-        entries.insert("R5".to_string(), 5);
-        entries.insert("R6".to_string(), 6);
-        entries.insert("R7".to_string(), 7);
-        entries.insert("R8".to_string(), 8);
-        entries.insert("R9".to_string(), 9);
-        entries.insert("R10".to_string(), 10);
-        entries.insert("R11".to_string(), 11);
-        entries.insert("R12".to_string(), 12);
-        entries.insert("R13".to_string(), 13);
-        entries.insert("R14".to_string(), 14);
-        entries.insert("R15".to_string(), 15);
-
-        entries.insert("SP".to_string(), 0);
-        entries.insert("LCL".to_string(), 1);
-        entries.insert("ARG".to_string(), 2);
-        entries.insert("THIS".to_string(), 3);
-        entries.insert("THAT".to_string(), 4);
-        entries.insert("SCREEN".to_string(), 16384);
-        entries.insert("KBD".to_string(), 24576);
-
-        Self { entries }
+        let mut table = Self::empty();
+        for (symbol, address) in PREDEFINED_SYMBOLS {
+            table.add_predefined(symbol, address);
+        }
+        table
+    }
+
+    // 定義済みのシンボルを持たない空の表
+    pub fn empty() -> Self {
+        Self {
+            symbols: Vec::new(),
+            indexes: HashMap::new(),
+        }
     }
 
+    // 定義済みシンボルとして登録する。既にある定義済みシンボルはアドレスを上書きする
+    pub fn add_predefined(&mut self, symbol: &str, address: u16) {
+        self.insert(symbol, address, SymbolKind::Predefined, 1);
+    }
+
+    // 変数として登録する
     pub fn add_entry(&mut self, symbol: &str, address: u16) -> Result<()> {
-        self.entries.insert(symbol.to_string(), address);
+        self.add_symbol(symbol, address, SymbolKind::Variable)
+    }
+
+    // シンボルは再定義できず、定義済みシンボルや種別の違う既存のシンボルと同じ名前も使えない
+    pub fn add_symbol(&mut self, symbol: &str, address: u16, kind: SymbolKind) -> Result<()> {
+        if let Some(existing) = self.kind(symbol) {
+            bail!(ErrorCode::DuplicateSymbol.error(tr!(
+                already_defined,
+                symbol,
+                existing.description()
+            )));
+        }
+        self.insert(symbol, address, kind, 1);
         Ok(())
     }
 
+    // sizeワードの領域を割り当てた変数として登録する
+    pub fn add_variable(&mut self, symbol: &str, address: u16, size: u16) -> Result<()> {
+        self.add_symbol(symbol, address, SymbolKind::Variable)?;
+        self.insert(symbol, address, SymbolKind::Variable, size);
+        Ok(())
+    }
+
+    // 既に登録されているシンボルは登録順と参照位置を保ったまま上書きする
+    fn insert(&mut self, symbol: &str, address: u16, kind: SymbolKind, size: u16) {
+        match self.indexes.get(symbol) {
+            Some(index) => {
+                let info = &mut self.symbols[*index];
+                info.kind = kind;
+                info.address = address;
+                info.size = size;
+            }
+            None => {
+                self.indexes.insert(symbol.to_string(), self.symbols.len());
+                self.symbols.push(SymbolInfo {
+                    name: symbol.to_string(),
+                    kind,
+                    address,
+                    size,
+                    definition: None,
+                    references: Vec::new(),
+                });
+            }
+        }
+    }
+
+    // シンボルを定義した位置を記録する
+    pub fn set_definition(&mut self, symbol: &str, location: Location) {
+        if let Some(index) = self.indexes.get(symbol) {
+            self.symbols[*index].definition = Some(location);
+        }
+    }
+
+    // シンボルを参照した位置を記録する
+    pub fn add_reference(&mut self, symbol: &str, location: Location) {
+        if let Some(index) = self.indexes.get(symbol) {
+            self.symbols[*index].references.push(location);
+        }
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&SymbolInfo> {
+        self.indexes.get(symbol).map(|index| &self.symbols[*index])
+    }
+
+    // 登録した順にシンボルを返す
+    pub fn iter(&self) -> impl Iterator<Item = &SymbolInfo> {
+        self.symbols.iter()
+    }
+
+    pub fn address(&self, symbol: &str) -> Option<u16> {
+        self.get(symbol).map(|info| info.address)
+    }
+
+    // シンボルに割り当てた領域のワード数
+    pub fn size(&self, symbol: &str) -> u16 {
+        self.get(symbol).map_or(1, |info| info.size)
+    }
+
+    pub fn references(&self, symbol: &str) -> &[Location] {
+        self.get(symbol).map_or(&[], |info| &info.references)
+    }
+
     pub fn contains(&self, symbol: &str) -> Result<bool> {
-        Ok(self.entries.contains_key(symbol))
+        Ok(self.indexes.contains_key(symbol))
     }
 
     pub fn get_address(&self, symbol: &str) -> Result<u16> {
-        Ok(*self.entries.get(symbol).unwrap())
+        Ok(self.address(symbol).unwrap())
+    }
+
+    pub fn kind(&self, symbol: &str) -> Option<SymbolKind> {
+        self.get(symbol).map(|info| info.kind)
+    }
+
+    // 指定した種別のシンボルをアドレス順に返す
+    pub fn symbols(&self, kind: SymbolKind) -> Vec<(String, u16)> {
+        let mut symbols: Vec<(String, u16)> = self
+            .iter()
+            .filter(|info| info.kind == kind)
+            .map(|info| (info.name.clone(), info.address))
+            .collect();
+        symbols.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        symbols
+    }
+
+    // symbolと名前が似ているシンボルを似ている順に返す。大文字小文字の違いは区別しない
+    pub fn similar_symbols(&self, symbol: &str) -> Vec<String> {
+        let symbol_lower = symbol.to_lowercase();
+        // 短い名前ほど偶然似やすいので、許す編集距離を名前の長さに応じて小さくする
+        let max_distance = MAX_SIMILAR_DISTANCE.min(symbol.chars().count() / 3);
+        let mut similar: Vec<(usize, &String)> = self
+            .iter()
+            .map(|info| &info.name)
+            .filter(|name| name.as_str() != symbol)
+            .map(|name| (strsim::levenshtein(&symbol_lower, &name.to_lowercase()), name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        similar.sort();
+        similar.into_iter().map(|(_, name)| name.clone()).collect()
     }
 }
 
@@ -59,9 +248,123 @@ mod tests {
     fn it_works() -> Result<()> {
         let mut table = SymbolTable::new();
         table.add_entry("sum", 5)?;
-        assert_eq!(table.entries.contains_key("sum"), true);
+        assert_eq!(table.contains("sum")?, true);
         assert_eq!(table.get_address("sum")?, 5);
         assert_eq!(table.contains("empty")?, false);
         Ok(())
     }
+
+    #[test]
+    fn test_kind() -> Result<()> {
+        let mut table = SymbolTable::new();
+        table.add_symbol("LOOP", 3, SymbolKind::Label)?;
+        table.add_symbol("WIDTH", 512, SymbolKind::Constant)?;
+        table.add_symbol("i", 16, SymbolKind::Variable)?;
+        assert_eq!(table.kind("SCREEN"), Some(SymbolKind::Predefined));
+        assert_eq!(table.kind("LOOP"), Some(SymbolKind::Label));
+        assert_eq!(table.kind("WIDTH"), Some(SymbolKind::Constant));
+        assert_eq!(table.kind("i"), Some(SymbolKind::Variable));
+        assert_eq!(table.kind("empty"), None);
+        assert_eq!(
+            table.symbols(SymbolKind::Constant),
+            vec![("WIDTH".to_string(), 512)]
+        );
+
+        //再定義や既存のシンボルと同じ名前はエラー
+        assert!(table.add_symbol("WIDTH", 256, SymbolKind::Constant).is_err());
+        assert!(table.add_symbol("WIDTH", 5, SymbolKind::Label).is_err());
+        assert!(table.add_symbol("LOOP", 1, SymbolKind::Constant).is_err());
+        assert!(table.add_symbol("KBD", 1, SymbolKind::Constant).is_err());
+        let error = table.add_symbol("LOOP", 7, SymbolKind::Label).unwrap_err();
+        assert_eq!(
+            error_code::code_of(&error),
+            Some(ErrorCode::DuplicateSymbol)
+        );
+        assert!(table.add_symbol("i", 7, SymbolKind::Label).is_err());
+        assert!(table.add_symbol("SCREEN", 7, SymbolKind::Label).is_err());
+        assert!(table.add_variable("LOOP", 20, 1).is_err());
+        assert_eq!(table.address("LOOP"), Some(3));
+        assert_eq!(table.kind("i"), Some(SymbolKind::Variable));
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbol_info() -> Result<()> {
+        let mut table = SymbolTable::empty();
+        table.add_symbol("LOOP", 3, SymbolKind::Label)?;
+        table.add_variable("buffer", 16, 64)?;
+        table.add_symbol("END", 1, SymbolKind::Label)?;
+        table.set_definition("LOOP", Location::new(4));
+        table.add_reference("LOOP", Location::new(8));
+        table.add_reference("LOOP", Location::new(9));
+        table.add_reference("missing", Location::new(10));
+
+        //登録した順に列挙する
+        let names: Vec<&str> = table.iter().map(|info| info.name.as_str()).collect();
+        assert_eq!(names, vec!["LOOP", "buffer", "END"]);
+
+        assert_eq!(
+            table.get("LOOP"),
+            Some(&SymbolInfo {
+                name: "LOOP".to_string(),
+                kind: SymbolKind::Label,
+                address: 3,
+                size: 1,
+                definition: Some(Location::new(4)),
+                references: vec![Location::new(8), Location::new(9)],
+            })
+        );
+        assert_eq!(table.references("buffer"), &[]);
+        assert_eq!(table.references("missing"), &[]);
+        assert_eq!(table.address("END"), Some(1));
+        assert_eq!(table.address("missing"), None);
+
+        //再定義は失敗し、アドレスと参照位置は変わらない
+        assert!(table.add_symbol("LOOP", 5, SymbolKind::Label).is_err());
+        assert_eq!(table.iter().next().unwrap().address, 3);
+        assert_eq!(table.references("LOOP").len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_predefined() {
+        let mut table = SymbolTable::empty();
+        assert!(!table.contains("SCREEN").unwrap());
+        table.add_predefined("LED", 0x6001);
+        assert_eq!(table.kind("LED"), Some(SymbolKind::Predefined));
+
+        let mut table = SymbolTable::new();
+        assert_eq!(table.get_address("R15").unwrap(), 15);
+        table.add_predefined("KBD", 0x6000 + 8);
+        assert_eq!(table.get_address("KBD").unwrap(), 0x6008);
+    }
+
+    #[test]
+    fn test_add_variable() -> Result<()> {
+        let mut table = SymbolTable::new();
+        table.add_variable("buffer", 16, 64)?;
+        table.add_variable("i", 80, 1)?;
+        assert_eq!(table.kind("buffer"), Some(SymbolKind::Variable));
+        assert_eq!(table.size("buffer"), 64);
+        assert_eq!(table.size("i"), 1);
+        assert_eq!(table.size("SCREEN"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_similar_symbols() -> Result<()> {
+        let mut table = SymbolTable::new();
+        table.add_symbol("LOOP", 3, SymbolKind::Label)?;
+        table.add_symbol("counter", 16, SymbolKind::Variable)?;
+        table.add_symbol("count", 17, SymbolKind::Variable)?;
+        assert_eq!(table.similar_symbols("loop"), vec!["LOOP"]);
+        assert_eq!(table.similar_symbols("coutner"), vec!["counter"]);
+        assert_eq!(table.similar_symbols("counte"), vec!["count", "counter"]);
+        assert_eq!(table.similar_symbols("screen"), vec!["SCREEN"]);
+        //短い名前は大文字小文字の違いだけを似ているとみなす
+        assert!(table.similar_symbols("SQ").is_empty());
+        //自分自身は含めない
+        assert_eq!(table.similar_symbols("counter"), vec!["count"]);
+        Ok(())
+    }
 }